@@ -0,0 +1,107 @@
+//! Run with `cargo bench --bench adjacency` to compare the compressed sparse row adjacency with
+//! the `Vec` of approvers per vertex it replaced. The heap memory of both layouts is printed
+//! before the measurements.
+
+use std::{
+    collections::VecDeque,
+    mem::{size_of, size_of_val},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use ledger::{
+    generator::{generate_vertices, GeneratorConfig},
+    graph::{find_inward_references, Adjacency, Graph, VertexWithStats},
+};
+
+type Id = usize;
+
+fn graph(count: usize) -> Vec<VertexWithStats> {
+    let vertices = generate_vertices(&GeneratorConfig {
+        vertices: count,
+        window: 10,
+        max_time_step: 2,
+        seed: 0,
+    });
+    Graph::new(vertices).graph
+}
+
+/// The previous layout: the approvers pushed to the `Vec` of every parent
+fn vec_per_vertex(graph: &[VertexWithStats]) -> Vec<Vec<Id>> {
+    let mut inbounds = vec![vec![]; graph.len()];
+    for (idx, vertex) in graph.iter().enumerate() {
+        for parent in vertex.vertex.parents {
+            inbounds[parent - 1].push(idx + 1);
+        }
+    }
+    inbounds
+}
+
+fn vec_per_vertex_heap_size(inbounds: &[Vec<Id>]) -> usize {
+    size_of_val(inbounds)
+        + inbounds
+            .iter()
+            .map(|approvers| approvers.capacity() * size_of::<Id>())
+            .sum::<usize>()
+}
+
+/// The BFS from the root, the same for both layouts
+fn root_depths<I>(count: usize, approvers: impl Fn(Id) -> I) -> Vec<usize>
+where
+    I: Iterator<Item = Id>,
+{
+    let mut depths = vec![usize::MAX; count];
+    let mut queue = VecDeque::from([(0, 1)]);
+    while let Some((depth, id)) = queue.pop_front() {
+        if depths[id - 1] != usize::MAX {
+            continue;
+        }
+        depths[id - 1] = depth;
+        queue.extend(approvers(id).map(|approver| (depth + 1, approver)));
+    }
+    depths
+}
+
+fn adjacency(c: &mut Criterion) {
+    let mut build = c.benchmark_group("build_adjacency");
+    build.sample_size(10);
+    let graphs: Vec<_> = [100_000, 1_000_000].into_iter().map(graph).collect();
+    for graph in &graphs {
+        let csr = find_inward_references(graph).expect("valid graph");
+        let vecs = vec_per_vertex(graph);
+        println!(
+            "heap memory of {} vertices: vec per vertex {} B, csr {} B",
+            graph.len(),
+            vec_per_vertex_heap_size(&vecs),
+            csr.heap_size()
+        );
+
+        build.bench_with_input(
+            BenchmarkId::new("vec_per_vertex", graph.len()),
+            graph,
+            |b, graph| b.iter(|| vec_per_vertex(graph)),
+        );
+        build.bench_with_input(BenchmarkId::new("csr", graph.len()), graph, |b, graph| {
+            b.iter(|| Adjacency::build(graph).expect("valid graph"))
+        });
+    }
+    build.finish();
+
+    let mut bfs = c.benchmark_group("root_depth_bfs");
+    bfs.sample_size(10);
+    for graph in &graphs {
+        let csr = find_inward_references(graph).expect("valid graph");
+        let vecs = vec_per_vertex(graph);
+        bfs.bench_with_input(
+            BenchmarkId::new("vec_per_vertex", graph.len()),
+            &vecs,
+            |b, vecs| b.iter(|| root_depths(vecs.len(), |id| vecs[id - 1].iter().copied())),
+        );
+        bfs.bench_with_input(BenchmarkId::new("csr", graph.len()), &csr, |b, csr| {
+            b.iter(|| root_depths(csr.len(), |id| csr.approvers(id)))
+        });
+    }
+    bfs.finish();
+}
+
+criterion_group!(benches, adjacency);
+criterion_main!(benches);
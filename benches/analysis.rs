@@ -0,0 +1,88 @@
+//! Run with `cargo bench --features parallel` to compare the sequential and the parallel
+//! analysis. The cumulative weight is measured on a generated ledger and on a sparse graph of
+//! many short chains.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use ledger::{
+    generator::{generate_vertices, GeneratorConfig},
+    graph::{Graph, UnreachablePolicy},
+    vertex::{Parents, Vertex},
+};
+
+fn vertices(count: usize) -> Vec<Vertex> {
+    generate_vertices(&GeneratorConfig {
+        vertices: count,
+        window: 10,
+        max_time_step: 2,
+        seed: 0,
+    })
+}
+
+/// Chains of 100 vertices, each starting with a vertex without parents
+fn chains(count: usize) -> Vec<Vertex> {
+    (0..count)
+        .map(|idx| Vertex {
+            parents: match idx % 100 {
+                0 => Parents::default(),
+                _ => Parents::from([idx]),
+            },
+            timestamp: 0,
+        })
+        .collect()
+}
+
+fn walk_and_analyze(c: &mut Criterion) {
+    let mut group = c.benchmark_group("walk_and_analyze");
+    group.sample_size(10);
+    for count in [100_000, 1_000_000] {
+        let vertices = vertices(count);
+        group.bench_with_input(
+            BenchmarkId::new("sequential", count),
+            &vertices,
+            |b, vertices| {
+                b.iter_batched(
+                    || Graph::new(vertices.clone()),
+                    |mut graph| graph.walk_and_analyze().expect("valid graph"),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+        #[cfg(feature = "parallel")]
+        group.bench_with_input(
+            BenchmarkId::new("parallel", count),
+            &vertices,
+            |b, vertices| {
+                b.iter_batched(
+                    || Graph::new(vertices.clone()),
+                    |mut graph| graph.walk_and_analyze_parallel().expect("valid graph"),
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn cumulative_weight(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cumulative_weight");
+    group.sample_size(10);
+    let count = 1_000_000;
+    for (name, vertices) in [("ledger", vertices(count)), ("chains", chains(count))] {
+        group.bench_with_input(BenchmarkId::new(name, count), &vertices, |b, vertices| {
+            b.iter_batched(
+                || {
+                    let mut graph = Graph::new(vertices.clone())
+                        .with_unreachable_policy(UnreachablePolicy::SeparateComponents);
+                    graph.walk_and_analyze().expect("valid graph");
+                    graph
+                },
+                |mut graph| graph.analyze_cumulative_weight().expect("valid graph"),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, walk_and_analyze, cumulative_weight);
+criterion_main!(benches);
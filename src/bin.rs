@@ -1,24 +1,564 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    time::Duration,
+};
+
 use clap::Parser;
+use serde::Serialize;
 
-use crate::{cli::CliOptions, graph::Graph};
+use crate::cli::{
+    CliOptions, Command, ConeOptions, ConfirmationOptions, ConvertOptions, DatabaseOptions,
+    DiffFormat, DiffOptions, ExportFormat, ExportOptions, GenerateOptions, InputFormat,
+    PruneOptions, QueryOptions, StatsFormat, StatsOptions, TimestampValidation, ValidateOptions,
+};
+use ledger::{
+    binary_database, database, diagnostics,
+    diff::{self, Diff},
+    error::LedgerError,
+    export::{self, DotOptions},
+    follow::FollowRows,
+    generator::{self, GeneratorConfig},
+    graph::{
+        ConfirmationConfig, ConfirmationStatus, ConfirmationThreshold, Graph, GraphStats, RootStats,
+    },
+    interchange,
+    prune::{self, Cut},
+    vertex::{RowFormat, Vertex},
+};
 mod cli;
-mod database;
-mod graph;
-mod vertex;
 
 fn main() {
-    let cfg = CliOptions::parse();
+    match CliOptions::parse().command() {
+        Command::Stats(options) if options.follow => follow_stats(&options),
+        Command::Stats(options) => print_stats(&load_graph(&options.database), &options),
+        Command::Validate(options) => validate(&options),
+        Command::Query(options) => print_vertex(&load_graph(&options.database), &options),
+        Command::Confirmations(options) => {
+            print_unconfirmed(&mut load_graph(&options.database), &options)
+        }
+        Command::Cone(options) => print_cones(&load_graph(&options.database), &options),
+        Command::Export(options) => export(&load_graph(&options.database), &options),
+        Command::Generate(options) => generate(&options),
+        Command::Convert(options) => convert(&options),
+        Command::Prune(options) => prune(&load_graph(&options.database), &options),
+        Command::Diff(options) => print_diff(&options),
+    }
+}
+
+/// Loads, validates and analyzes the graph. The program exits if the graph is invalid.
+fn load_graph(options: &DatabaseOptions) -> Graph {
+    let vertices = match load_vertices(
+        &options.database_file_path,
+        options.input_format,
+        options.row_format.into(),
+    ) {
+        Ok(vertices) => vertices,
+        Err(err) => {
+            eprintln!("{}: {err}", options.database_file_path);
+            std::process::exit(1);
+        }
+    };
+    let mut graph = Graph::new(vertices)
+        .with_roots(options.roots.clone())
+        .with_unreachable_policy(options.unreachable.into());
+    let report = graph.validate();
+    // the forward references are only warnings
+    if !report.is_empty() {
+        eprint!("{report}");
+    }
+    if !report.is_valid() {
+        std::process::exit(1);
+    }
+    check_roots(&graph);
+    if options.validate_timestamps != TimestampValidation::Off {
+        let violations = graph.validate_timestamps(options.check_file_order);
+        for violation in &violations {
+            eprintln!("{violation}");
+        }
+        if options.validate_timestamps == TimestampValidation::Strict && !violations.is_empty() {
+            std::process::exit(1);
+        }
+    }
+    #[cfg(feature = "parallel")]
+    let analyzed = graph.walk_and_analyze_parallel();
+    #[cfg(not(feature = "parallel"))]
+    let analyzed = graph.walk_and_analyze();
+    if let Err(err) = analyzed {
+        eprintln!("{}: {err}", options.database_file_path);
+        std::process::exit(1);
+    }
+    if !graph.unreachable_vertices().is_empty() {
+        eprintln!(
+            "vertices unreachable from the root: {:?}",
+            graph.unreachable_vertices()
+        );
+    }
+    graph
+}
+
+fn load_vertices(
+    path: &str,
+    format: InputFormat,
+    row_format: RowFormat,
+) -> Result<Vec<Vertex>, LedgerError> {
+    match format {
+        InputFormat::Text => {
+            database::read_vertices_with_format(database::open_database(path)?, row_format)
+        }
+        InputFormat::Graphml => interchange::read_graphml(database::open_database(path)?),
+        InputFormat::Json => interchange::read_node_link_json(database::open_database(path)?),
+        InputFormat::Binary => binary_database::load_vertices_from_binary_database(path, true),
+    }
+}
+
+/// Exits if any of the roots isn't in the graph. The analysis would skip it, as it could be
+/// appended later.
+fn check_roots(graph: &Graph) {
+    let max_id = graph.graph.len();
+    if let Some(id) = graph.roots().iter().find(|id| graph.vertex(**id).is_none()) {
+        eprintln!("{}", LedgerError::InvalidVertexId { id: *id, max_id });
+        std::process::exit(1);
+    }
+}
+
+/// Opens the output file or the standard output
+fn open_output(path: &Option<String>) -> Box<dyn Write> {
+    match path {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).expect("creating output file failed"),
+        )),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    }
+}
+
+/// The statistics of the whole graph followed by the ones of each root, if requested
+#[derive(Serialize)]
+struct StatsWithRoots<'a> {
+    #[serde(flatten)]
+    stats: &'a GraphStats,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    roots: &'a [RootStats],
+}
+
+fn print_stats(graph: &Graph, options: &StatsOptions) {
+    let stats = graph.stats();
+    let roots = match options.per_root {
+        true => graph.root_stats(),
+        false => vec![],
+    };
+    let with_roots = StatsWithRoots {
+        stats: &stats,
+        roots: &roots,
+    };
+    match options.format {
+        StatsFormat::Text => {
+            print_stats_text(&stats);
+            for root in &roots {
+                println!();
+                println!("ROOT: {}", root.root);
+                print_stats_text(&root.stats);
+            }
+        }
+        StatsFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&with_roots).expect("serializing stats failed")
+        ),
+        StatsFormat::Csv if options.per_root => {
+            // the row of the whole graph has no root
+            print!("root,");
+            print_stats_csv_header();
+            print!(",");
+            print_stats_csv(&stats);
+            for root in &roots {
+                print!("{},", root.root);
+                print_stats_csv(&root.stats);
+            }
+        }
+        StatsFormat::Csv => {
+            print_stats_csv_header();
+            print_stats_csv(&stats);
+        }
+        StatsFormat::Toml => print_stats_toml(&with_roots),
+    }
+}
+
+/// Reads the rows appended to the database and prints the statistics at the interval. The
+/// graph is updated incrementally, so only the structure of the new vertices is validated.
+fn follow_stats(options: &StatsOptions) {
+    let database = &options.database;
+    if database.input_format != InputFormat::Text {
+        eprintln!("only the text database can be followed");
+        std::process::exit(1);
+    }
+    let reader =
+        database::open_database(&database.database_file_path).expect("opening database failed");
+    let mut rows = FollowRows::new(reader).with_row_format(database.row_format.into());
+    let mut graph = Graph::new(vec![Vertex::default()])
+        .with_roots(database.roots.clone())
+        .with_unreachable_policy(database.unreachable.into());
+    let interval = Duration::try_from_secs_f64(options.interval).expect("invalid interval");
+
+    let mut reserved = false;
+    for iteration in 0.. {
+        while let Some(row) = rows.next() {
+            let id = match row.and_then(|vertex| graph.push_vertex(vertex)) {
+                Ok(id) => id,
+                Err(err) => {
+                    eprintln!("{err}");
+                    std::process::exit(1);
+                }
+            };
+            if !reserved {
+                // the header is read along with the first row
+                let declared = rows.declared_vertices().unwrap_or_default();
+                graph.reserve(declared.min(database::MAX_PREALLOCATED));
+                reserved = true;
+            }
+            if database.validate_timestamps != TimestampValidation::Off {
+                let violations = graph.validate_vertex_timestamps(id, database.check_file_order);
+                for violation in &violations {
+                    eprintln!("{violation}");
+                }
+                if database.validate_timestamps == TimestampValidation::Strict
+                    && !violations.is_empty()
+                {
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        let stats = graph.stats();
+        match options.format {
+            StatsFormat::Text if iteration == 0 => print_stats_text(&stats),
+            StatsFormat::Text => {
+                println!();
+                print_stats_text(&stats);
+            }
+            // JSON lines
+            StatsFormat::Json => println!(
+                "{}",
+                serde_json::to_string(&stats).expect("serializing stats failed")
+            ),
+            StatsFormat::Csv if iteration == 0 => {
+                print_stats_csv_header();
+                print_stats_csv(&stats);
+            }
+            StatsFormat::Csv => print_stats_csv(&stats),
+            StatsFormat::Toml if iteration == 0 => print_stats_toml(&stats),
+            StatsFormat::Toml => {
+                println!();
+                print_stats_toml(&stats);
+            }
+        }
+        std::io::stdout().flush().expect("writing stats failed");
+        std::thread::sleep(interval);
+    }
+}
+
+fn print_stats_text(stats: &GraphStats) {
+    println!("AVG DAG DEPTH: {:.2}", stats.avg_root_depth_per_node);
+    println!(
+        "AVG NODES PER DEPTH:  {:.2}",
+        stats.avg_nodes_per_root_depth
+    );
+    println!("AVG REF:  {:.2}", stats.avg_inbound_ref_per_node);
+    println!("TIPS: {}", stats.tips);
+    println!("TIP RATIO: {:.2}", stats.tip_ratio);
+    println!("AVG TIP AGE: {:.2}", stats.avg_tip_age);
+}
+
+fn print_stats_toml(stats: &impl Serialize) {
+    print!(
+        "{}",
+        toml::to_string(stats).expect("serializing stats failed")
+    );
+}
+
+fn print_stats_csv_header() {
+    println!(
+        "vertices,unreachable_vertices,avg_root_depth_per_node,avg_nodes_per_root_depth,\
+        avg_inbound_ref_per_node,tips,tip_ratio,avg_tip_age"
+    );
+}
+
+fn print_stats_csv(stats: &GraphStats) {
+    println!(
+        "{},{},{},{},{},{},{},{}",
+        stats.vertices,
+        stats.unreachable_vertices,
+        stats.avg_root_depth_per_node,
+        stats.avg_nodes_per_root_depth,
+        stats.avg_inbound_ref_per_node,
+        stats.tips,
+        stats.tip_ratio,
+        stats.avg_tip_age
+    );
+}
 
-    let vertices = database::load_vertices_from_database(&cfg.database_file_path)
-        .expect("loading vertices for graph failed");
-    let mut graph = Graph::new(vertices);
-    graph.walk_and_analyze().expect("invalid graph");
+/// Reports all the issues of the database and exits with an error if there are any
+fn validate(options: &ValidateOptions) {
+    let database = &options.database;
+    let path = &database.database_file_path;
+    let loaded = match database.input_format {
+        // the text database is parsed leniently to report all the malformed rows at once
+        InputFormat::Text => database::open_database(path).and_then(|reader| {
+            diagnostics::read_vertices_lenient(reader, database.row_format.into())
+        }),
+        format => load_vertices(path, format, database.row_format.into())
+            .map(|vertices| (vertices, vec![])),
+    };
+    let vertices = match loaded {
+        Ok((vertices, diagnostics)) if diagnostics.is_empty() => vertices,
+        Ok((_, diagnostics)) => {
+            diagnostics::render_diagnostics(&diagnostics, path, std::io::stderr().lock())
+                .expect("writing diagnostics failed");
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let mut graph = Graph::new(vertices)
+        .with_roots(database.roots.clone())
+        .with_unreachable_policy(database.unreachable.into());
 
-    let avg_inbound_ref_per_node = graph.calc_avg_inbound_ref_per_node();
-    let avg_nodes_per_depth = graph.calc_avg_nodes_per_root_depth();
-    let avg_depth_per_node = graph.calc_avg_root_depth_per_node();
+    let report = graph.validate();
+    let violations = match database.validate_timestamps {
+        TimestampValidation::Off => vec![],
+        _ => graph.validate_timestamps(database.check_file_order),
+    };
+    for violation in &violations {
+        eprintln!("{violation}");
+    }
+    if !report.is_empty() {
+        eprint!("{report}");
+    }
+    if !report.is_valid() {
+        std::process::exit(1);
+    }
+    check_roots(&graph);
+    if let Err(err) = graph.walk_and_analyze() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+    if !graph.unreachable_vertices().is_empty() {
+        eprintln!(
+            "vertices unreachable from the root: {:?}",
+            graph.unreachable_vertices()
+        );
+    }
+    if database.validate_timestamps == TimestampValidation::Strict && !violations.is_empty() {
+        std::process::exit(1);
+    }
+    println!("the database is valid");
+}
+
+fn print_vertex(graph: &Graph, options: &QueryOptions) {
+    let Some(vertex) = graph.vertex(options.id) else {
+        let max_id = graph.graph.len();
+        eprintln!(
+            "{}",
+            LedgerError::InvalidVertexId {
+                id: options.id,
+                max_id
+            }
+        );
+        std::process::exit(1);
+    };
+    let mut inbounds: Vec<usize> = graph.approvers(options.id).collect();
+    inbounds.dedup();
+
+    println!("ID: {}", options.id);
+    println!("PARENTS: {:?}", vertex.vertex.parents);
+    println!("TIMESTAMP: {}", vertex.vertex.timestamp);
+    if vertex.visited {
+        println!("ROOT DEPTH: {}", vertex.root_depth);
+    }
+    println!("INBOUNDS: {inbounds:?}");
+    println!("TIP: {}", inbounds.is_empty());
+}
+
+fn print_unconfirmed(graph: &mut Graph, options: &ConfirmationOptions) {
+    let config = ConfirmationConfig::from(options);
+    if let ConfirmationThreshold::Weight(_) = config.threshold {
+        graph
+            .analyze_cumulative_weight()
+            .expect("finding cumulative weight failed");
+    }
+    let statuses = graph
+        .classify_confirmations(&config)
+        .expect("classifying confirmations failed");
+
+    for (id, status) in statuses {
+        if status != ConfirmationStatus::Confirmed {
+            println!("{id} {status}");
+        }
+    }
+}
+
+fn print_cones(graph: &Graph, options: &ConeOptions) {
+    let cones = graph.past_cone(options.id).and_then(|past| {
+        let future = graph.future_cone(options.id)?;
+        Ok((past.collect::<Vec<_>>(), future.collect::<Vec<_>>()))
+    });
+    let (mut past, mut future) = match cones {
+        Ok(cones) => cones,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if options.ids {
+        past.sort_unstable();
+        future.sort_unstable();
+        println!("PAST CONE: {past:?}");
+        println!("FUTURE CONE: {future:?}");
+    } else {
+        println!("PAST CONE: {}", past.len());
+        println!("FUTURE CONE: {}", future.len());
+    }
+}
+
+fn export(graph: &Graph, options: &ExportOptions) {
+    let mut output = open_output(&options.output);
+    match options.format {
+        ExportFormat::Csv => export::write_csv(graph, &mut output).map_err(LedgerError::from),
+        ExportFormat::Dot => export::write_dot(graph, &DotOptions::from(&options.dot), &mut output),
+        ExportFormat::Graphml => interchange::write_graphml(vertices(graph), &mut output),
+        ExportFormat::Json => interchange::write_node_link_json(vertices(graph), &mut output),
+        ExportFormat::Text => database::write_vertices_with_format(
+            &mut output,
+            &vertices(graph).cloned().collect::<Vec<_>>(),
+            options.output_row_format.into(),
+        ),
+        ExportFormat::Binary => binary_database::write_vertices_binary(
+            &mut output,
+            &vertices(graph).cloned().collect::<Vec<_>>(),
+        ),
+    }
+    .and_then(|_| Ok(output.flush()?))
+    .expect("exporting graph failed");
+}
+
+fn vertices(graph: &Graph) -> impl Iterator<Item = &Vertex> + Clone {
+    graph.graph.iter().map(|vertex| &vertex.vertex)
+}
+
+fn generate(options: &GenerateOptions) {
+    let vertices = generator::generate_vertices(&GeneratorConfig {
+        vertices: options.vertices,
+        window: options.window,
+        max_time_step: options.max_time_step,
+        seed: options.seed,
+    });
+
+    let mut output = open_output(&options.output);
+    database::write_vertices(&mut output, &vertices)
+        .and_then(|_| Ok(output.flush()?))
+        .expect("writing generated database failed");
+}
+
+fn convert(options: &ConvertOptions) {
+    binary_database::convert_text_to_binary(
+        &options.database_file_path,
+        &options.output,
+        options.row_format.into(),
+    )
+    .expect("converting database failed");
+}
+
+fn prune(graph: &Graph, options: &PruneOptions) {
+    let snapshot = prune::prune(graph, Cut::from(options));
+
+    let mut output = open_output(&options.output);
+    database::write_vertices_with_format(
+        &mut output,
+        &snapshot.vertices,
+        options.output_row_format.into(),
+    )
+    .and_then(|_| Ok(output.flush()?))
+    .expect("writing pruned database failed");
+
+    let mut id_mapping = open_output(&Some(options.id_mapping.clone()));
+    snapshot
+        .write_id_mapping(&mut id_mapping)
+        .and_then(|_| id_mapping.flush())
+        .expect("writing ID mapping failed");
+
+    let roots: Vec<String> = snapshot.roots.iter().map(|id| id.to_string()).collect();
+    eprintln!("roots of the pruned database: --root {}", roots.join(","));
+}
+
+fn print_diff(options: &DiffOptions) {
+    let before = load_graph(&options.before_database());
+    let after = load_graph(&options.after_database());
+    let diff = match diff::diff(&before, &after, options.match_by.into()) {
+        Ok(diff) => diff,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    match options.format {
+        DiffFormat::Text => print_diff_text(&diff, &before, &after),
+        DiffFormat::Json => println!(
+            "{}",
+            serde_json::to_string(&diff).expect("serializing diff failed")
+        ),
+    }
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+}
 
-    println!("AVG DAG DEPTH: {:.2}", avg_depth_per_node);
-    println!("AVG NODES PER DEPTH:  {:.2}", avg_nodes_per_depth);
-    println!("AVG REF:  {:.2}", avg_inbound_ref_per_node);
+fn print_diff_text(diff: &Diff, before: &Graph, after: &Graph) {
+    println!("ADDED: {:?}", diff.added);
+    println!("REMOVED: {:?}", diff.removed);
+    println!("CHANGED: {}", diff.changed.len());
+    for matched in &diff.changed {
+        let (vertex, other) = (
+            &before.graph[matched.before - 1].vertex,
+            &after.graph[matched.after - 1].vertex,
+        );
+        println!(
+            "  {} -> {}: {:?} {} -> {:?} {}",
+            matched.before,
+            matched.after,
+            vertex.parents,
+            vertex.timestamp,
+            other.parents,
+            other.timestamp
+        );
+    }
+    if !diff.moved.is_empty() {
+        let moved: Vec<_> = diff
+            .moved
+            .iter()
+            .map(|matched| format!("{} -> {}", matched.before, matched.after))
+            .collect();
+        println!("MOVED: {}", moved.join(", "));
+    }
+    for (title, edges) in [
+        ("ADDED EDGES", &diff.added_edges),
+        ("REMOVED EDGES", &diff.removed_edges),
+    ] {
+        let edges: Vec<_> = edges
+            .iter()
+            .map(|edge| format!("{} -> {}", edge.approver, edge.parent))
+            .collect();
+        println!("{title}: [{}]", edges.join(", "));
+    }
+    println!("STATS:");
+    for stat in &diff.stats {
+        println!(
+            "  {}: {:.2} -> {:.2} ({:+.2})",
+            stat.name,
+            stat.before,
+            stat.after,
+            stat.after - stat.before
+        );
+    }
 }
@@ -0,0 +1,340 @@
+//! Compact binary database format. It consists of the header:
+//!
+//! | Offset | Size | Field |
+//! |--------|------|-------|
+//! | 0 | 8 | magic `LEDGERDB` |
+//! | 8 | 4 | format version |
+//! | 12 | 4 | CRC32 of the records |
+//! | 16 | 8 | the number of records |
+//!
+//! followed by the records of `count parent... timestamp`: the number of parents as a `u8`,
+//! followed by the parents and the timestamp, each a little-endian `u32`. Like in the text
+//! format the root isn't stored, so the first record is the vertex with ID 2.
+//!
+//! The version 1 databases, which are still read, have fixed-width records of
+//! `left right timestamp` instead, where the missing edge is stored as 0, which is never a valid
+//! ID. They can store only the vertices with at most two parents.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+};
+
+use log::debug;
+use memmap2::Mmap;
+
+use crate::{
+    database::{self, Compression},
+    error::LedgerError,
+    vertex::{Parents, RowFormat, Vertex, MAX_PARENTS},
+};
+
+type Id = usize;
+
+const MAGIC: &[u8; 8] = b"LEDGERDB";
+const VERSION: u32 = 2;
+/// The version with the fixed-width records of at most two parents
+const TWO_PARENTS_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 24;
+const TWO_PARENTS_RECORD_SIZE: usize = 12;
+/// The size of the record of a vertex without parents
+const MIN_RECORD_SIZE: usize = 5;
+const FORMAT: &str = "binary database";
+
+/// Loads the vertices from the binary database. With `memory_map` the file is memory-mapped
+/// instead of being read into memory, and the only allocation is the result. The standard input
+/// ([`database::STDIN_PATH`]) and the compressed files are always read into memory.
+///
+/// The memory-mapped file must not be modified while it's loaded.
+pub fn load_vertices_from_binary_database(
+    database_file: &str,
+    memory_map: bool,
+) -> Result<Vec<Vertex>, LedgerError> {
+    if memory_map && database_file != database::STDIN_PATH {
+        // SAFETY: the map lives only during parsing and the file isn't modified by the program
+        let map = unsafe { Mmap::map(&File::open(database_file)?)? };
+        if Compression::detect(&map).is_none() {
+            return read_vertices_from_bytes(&map);
+        }
+    }
+    let mut bytes = vec![];
+    database::open_database(database_file)?.read_to_end(&mut bytes)?;
+    read_vertices_from_bytes(&bytes)
+}
+
+/// Parses the binary database. Like [`database::read_vertices`], the result starts with the
+/// root.
+pub fn read_vertices_from_bytes(bytes: &[u8]) -> Result<Vec<Vertex>, LedgerError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(invalid("the file is shorter than the header".to_string()));
+    }
+    let (header, records) = bytes.split_at(HEADER_SIZE);
+    if &header[0..8] != MAGIC {
+        return Err(invalid(
+            "the file doesn't start with the magic bytes".to_string(),
+        ));
+    }
+    let version = u32_at(header, 8);
+    let checksum = u32_at(header, 12);
+    let count = u64::from_le_bytes(header[16..24].try_into().expect("8 bytes")) as usize;
+    debug!("Extracted number of nodes in binary database: {count}");
+
+    let vertices = match version {
+        VERSION => read_records(records, count)?,
+        TWO_PARENTS_VERSION => read_two_parents_records(records, count)?,
+        _ => return Err(invalid(format!("unsupported version {version}"))),
+    };
+    if crc32fast::hash(records) != checksum {
+        return Err(invalid("the checksum doesn't match".to_string()));
+    }
+    Ok(vertices)
+}
+
+/// Parses the variable-width records of the current version
+fn read_records(records: &[u8], count: usize) -> Result<Vec<Vertex>, LedgerError> {
+    // the declared number isn't checked yet
+    let mut vertices = Vec::with_capacity(count.min(records.len() / MIN_RECORD_SIZE) + 1);
+    vertices.push(Vertex::default());
+    let mut rest = records;
+    for parsed in 0..count {
+        let truncated = LedgerError::HeaderMismatch {
+            declared: count,
+            actual: parsed,
+        };
+        let Some((parent_count, record)) = rest.split_first() else {
+            return Err(truncated);
+        };
+        let parent_count = *parent_count as usize;
+        if parent_count > MAX_PARENTS {
+            return Err(invalid(format!(
+                "the vertex with ID {} has {parent_count} parents, but at most {MAX_PARENTS} are \
+                supported",
+                parsed + 2
+            )));
+        }
+        let size = 4 * (parent_count + 1);
+        if record.len() < size {
+            return Err(truncated);
+        }
+        let mut parents = Parents::default();
+        for offset in (0..4 * parent_count).step_by(4) {
+            parents.push(u32_at(record, offset) as Id);
+        }
+        vertices.push(Vertex {
+            parents,
+            timestamp: u32_at(record, 4 * parent_count),
+        });
+        rest = &record[size..];
+    }
+    if !rest.is_empty() {
+        return Err(invalid(format!(
+            "{} bytes follow the declared {count} records",
+            rest.len()
+        )));
+    }
+    Ok(vertices)
+}
+
+/// Parses the fixed-width records of the version 1
+fn read_two_parents_records(records: &[u8], count: usize) -> Result<Vec<Vertex>, LedgerError> {
+    if count.checked_mul(TWO_PARENTS_RECORD_SIZE) != Some(records.len()) {
+        return Err(LedgerError::HeaderMismatch {
+            declared: count,
+            actual: records.len() / TWO_PARENTS_RECORD_SIZE,
+        });
+    }
+    let mut vertices = Vec::with_capacity(count + 1);
+    vertices.push(Vertex::default());
+    vertices.extend(records.chunks_exact(TWO_PARENTS_RECORD_SIZE).map(|record| {
+        let mut parents = Parents::default();
+        for parent in [id_at(record, 0), id_at(record, 4)].into_iter().flatten() {
+            parents.push(parent);
+        }
+        Vertex {
+            parents,
+            timestamp: u32_at(record, 8),
+        }
+    }));
+    Ok(vertices)
+}
+
+/// Writes the vertices in the binary format of the current version. The first vertex is the
+/// root, which isn't stored, so it must have no data. The IDs must fit in `u32`.
+pub fn write_vertices_binary(
+    mut writer: impl Write,
+    vertices: &[Vertex],
+) -> Result<(), LedgerError> {
+    let (root, stored) = vertices.split_first().ok_or(LedgerError::EmptyGraph)?;
+    if *root != Vertex::default() {
+        return Err(LedgerError::UnrepresentableVertex {
+            id: 1,
+            reason:
+                "the root isn't stored in the database, so it must have no edges and timestamp 0",
+        });
+    }
+
+    let mut records = Vec::with_capacity(stored.len() * TWO_PARENTS_RECORD_SIZE);
+    for (idx, vertex) in stored.iter().enumerate() {
+        let id = idx + 2;
+        // a vertex has at most `MAX_PARENTS` parents
+        records.push(vertex.parents.len() as u8);
+        for parent in vertex.parents.iter().copied() {
+            let parent = u32::try_from(parent).map_err(|_| LedgerError::UnrepresentableVertex {
+                id,
+                reason: "the IDs in the binary database must fit in 32 bits",
+            })?;
+            records.extend_from_slice(&parent.to_le_bytes());
+        }
+        records.extend_from_slice(&vertex.timestamp.to_le_bytes());
+    }
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&crc32fast::hash(&records).to_le_bytes())?;
+    writer.write_all(&(stored.len() as u64).to_le_bytes())?;
+    writer.write_all(&records)?;
+    Ok(())
+}
+
+/// Converts the text database with the rows in the format to the binary one
+pub fn convert_text_to_binary(
+    text_file: &str,
+    binary_file: &str,
+    row_format: RowFormat,
+) -> Result<(), LedgerError> {
+    let vertices =
+        database::read_vertices_with_format(database::open_database(text_file)?, row_format)?;
+    let mut writer = BufWriter::new(File::create(binary_file)?);
+    write_vertices_binary(&mut writer, &vertices)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[inline]
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"))
+}
+
+#[inline]
+fn id_at(bytes: &[u8], offset: usize) -> Option<Id> {
+    Some(u32_at(bytes, offset) as Id).filter(|id| *id != 0)
+}
+
+fn invalid(reason: String) -> LedgerError {
+    LedgerError::InvalidDocument {
+        format: FORMAT,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vertex::RowFormat;
+
+    fn vertices() -> Vec<Vertex> {
+        database::read_vertices("3\n1 1 0\n2 3 1\n4 4 7\n".as_bytes()).expect("valid database")
+    }
+
+    fn to_bytes(vertices: &[Vertex]) -> Vec<u8> {
+        let mut output = vec![];
+        write_vertices_binary(&mut output, vertices).expect("writing to vector");
+        output
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let bytes = to_bytes(&vertices());
+        // two parents, one parent and no parents
+        assert_eq!(HEADER_SIZE + 13 + 9 + 5, bytes.len());
+        assert_eq!(vertices(), read_vertices_from_bytes(&bytes).expect("valid"));
+    }
+
+    #[test]
+    fn test_binary_many_parents() {
+        let vertices = database::read_vertices_with_format(
+            "2\n3 1 1 1 0\n8 1 2 1 2 1 2 1 2 4\n".as_bytes(),
+            RowFormat::ParentCount,
+        )
+        .expect("valid database");
+        let bytes = to_bytes(&vertices);
+        assert_eq!(vertices, read_vertices_from_bytes(&bytes).expect("valid"));
+    }
+
+    #[test]
+    fn test_binary_version_1() {
+        let records: Vec<u8> = [[1, 1, 0], [2, 0, 1], [0, 0, 7]]
+            .iter()
+            .flatten()
+            .flat_map(|value: &u32| value.to_le_bytes())
+            .collect();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&TWO_PARENTS_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(&records).to_le_bytes());
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(&records);
+        assert_eq!(vertices(), read_vertices_from_bytes(&bytes).expect("valid"));
+    }
+
+    #[test]
+    fn test_load_binary_database_memory_mapped() {
+        let path = std::env::temp_dir().join(format!("ledger-{}.bin", std::process::id()));
+        let path = path.to_str().expect("UTF-8 path");
+        std::fs::write(path, to_bytes(&vertices())).expect("writing temporary file");
+
+        let mapped = load_vertices_from_binary_database(path, true);
+        let read = load_vertices_from_binary_database(path, false);
+        std::fs::remove_file(path).expect("removing temporary file");
+
+        assert_eq!(vertices(), mapped.expect("valid"));
+        assert_eq!(vertices(), read.expect("valid"));
+    }
+
+    #[test]
+    fn test_binary_checksum_mismatch() {
+        let mut bytes = to_bytes(&vertices());
+        *bytes.last_mut().expect("not empty") ^= 1;
+        let err = read_vertices_from_bytes(&bytes).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidDocument { ref reason, .. } if reason.contains("checksum")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_binary_truncated() {
+        let bytes = to_bytes(&vertices());
+        let err = read_vertices_from_bytes(&bytes[..bytes.len() - 1]).expect_err("should fail");
+        assert!(
+            matches!(
+                err,
+                LedgerError::HeaderMismatch {
+                    declared: 3,
+                    actual: 2
+                }
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_binary_too_many_parents() {
+        let mut bytes = to_bytes(&vertices());
+        // the first record has 9 parents instead of 1
+        bytes[HEADER_SIZE] = 9;
+        let err = read_vertices_from_bytes(&bytes).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidDocument { ref reason, .. } if reason.contains("9 parents")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_binary_invalid_magic() {
+        let err = read_vertices_from_bytes(&[0; HEADER_SIZE]).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidDocument { ref reason, .. } if reason.contains("magic")),
+            "{err}"
+        );
+    }
+}
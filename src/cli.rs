@@ -1,8 +1,490 @@
-use clap::{Parser, ValueHint};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum, ValueHint};
+use ledger::diff;
+use ledger::export::DotOptions;
+use ledger::graph::{self, ConfirmationConfig, ConfirmationThreshold};
+use ledger::prune::Cut;
+use ledger::vertex;
 
-#[derive(Parser, Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Parser, Clone, Debug, PartialEq)]
+#[clap(args_conflicts_with_subcommands = true)]
 pub struct CliOptions {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options of the `stats` command, which is run when no command is given
+    #[clap(flatten)]
+    pub stats: StatsOptions,
+}
+
+impl CliOptions {
+    /// Returns the command to run. `stats` is the default one.
+    pub fn command(self) -> Command {
+        self.command.unwrap_or(Command::Stats(self.stats))
+    }
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Prints the statistics of the graph (default)
+    Stats(StatsOptions),
+    /// Validates the database and reports all the issues found
+    Validate(ValidateOptions),
+    /// Prints the details of a single vertex
+    Query(QueryOptions),
+    /// Lists the vertices that aren't confirmed yet
+    Confirmations(ConfirmationOptions),
+    /// Prints the past cone (the approved vertices) and the future cone (the approving
+    /// vertices) of the vertex
+    Cone(ConeOptions),
+    /// Writes the analyzed graph in another format
+    Export(ExportOptions),
+    /// Generates a random ledger database
+    Generate(GenerateOptions),
+    /// Converts the text database to the binary one without analyzing it
+    Convert(ConvertOptions),
+    /// Prunes the history before a timestamp or a root depth and writes the rest as a new
+    /// database
+    Prune(PruneOptions),
+    /// Compares two databases and prints the added, removed and changed vertices and edges and
+    /// the differences of the statistics. Exits with 1 if the databases differ
+    Diff(DiffOptions),
+}
+
+#[derive(Args, Clone, Debug, Default, PartialEq)]
+pub struct StatsOptions {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The format of the statistics. The machine-readable formats keep the full precision
+    #[clap(long, value_enum, default_value_t)]
+    pub format: StatsFormat,
+
+    /// Keeps the database open and prints the statistics as the rows are appended. The JSON is
+    /// printed as JSON lines, and the header is only the lower bound of the number of vertices
+    #[clap(long)]
+    pub follow: bool,
+
+    /// The interval in seconds between printing the statistics with `--follow`
+    #[clap(long, default_value_t = 1.0, requires = "follow")]
+    pub interval: f64,
+
+    /// Additionally prints the statistics of the vertices nearest to each root
+    #[clap(long, conflicts_with = "follow")]
+    pub per_root: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatsFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+    Toml,
+}
+
+#[derive(Args, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DatabaseOptions {
     /// The path to file with database
     #[clap(value_parser, env, default_value = "database.txt", value_hint = ValueHint::FilePath)]
     pub database_file_path: String,
+
+    /// The format of the database
+    #[clap(long, value_enum, default_value_t)]
+    pub input_format: InputFormat,
+
+    /// The format of the rows of the text database
+    #[clap(long, value_enum, default_value_t)]
+    pub row_format: RowFormat,
+
+    /// The IDs of the genesis vertices, from which the root depth is measured. The depth of a
+    /// vertex is the shortest path to the nearest of them
+    #[clap(long = "root", value_delimiter = ',', default_value = "1")]
+    pub roots: Vec<usize>,
+
+    /// How to treat the vertices that cannot be reached from the roots
+    #[clap(long, value_enum, default_value_t)]
+    pub unreachable: UnreachablePolicy,
+
+    /// Checks that no vertex is older than its parents
+    #[clap(long, value_enum, default_value_t)]
+    pub validate_timestamps: TimestampValidation,
+
+    /// Additionally checks that the timestamps don't decrease in the order of the database
+    #[clap(long)]
+    pub check_file_order: bool,
+}
+
+/// Defines how the analysis treats the vertices that cannot be reached from the root
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnreachablePolicy {
+    /// The analysis fails and reports the unreachable vertices
+    #[default]
+    Error,
+    /// The unreachable vertices are left out of the statistics
+    Exclude,
+    /// Every vertex without parents is treated as the root of a separate component
+    SeparateComponents,
+}
+
+impl From<UnreachablePolicy> for graph::UnreachablePolicy {
+    fn from(policy: UnreachablePolicy) -> Self {
+        match policy {
+            UnreachablePolicy::Error => graph::UnreachablePolicy::Error,
+            UnreachablePolicy::Exclude => graph::UnreachablePolicy::Exclude,
+            UnreachablePolicy::SeparateComponents => graph::UnreachablePolicy::SeparateComponents,
+        }
+    }
+}
+
+/// The format of the database rows
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RowFormat {
+    /// `left right timestamp`
+    #[default]
+    TwoParents,
+    /// `count parent... timestamp` with up to 8 parents
+    ParentCount,
+}
+
+impl From<RowFormat> for vertex::RowFormat {
+    fn from(format: RowFormat) -> Self {
+        match format {
+            RowFormat::TwoParents => vertex::RowFormat::TwoParents,
+            RowFormat::ParentCount => vertex::RowFormat::ParentCount,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// The number of vertices followed by the rows in the `--row-format`
+    #[default]
+    Text,
+    /// GraphML with the `timestamp` attribute of nodes
+    Graphml,
+    /// Node-link JSON
+    Json,
+    /// The binary database, which is memory-mapped
+    Binary,
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct ValidateOptions {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct QueryOptions {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The ID of the vertex
+    #[clap(long)]
+    pub id: usize,
+}
+
+#[derive(Args, Clone, Debug, PartialEq)]
+#[clap(group(ArgGroup::new("threshold").required(true)))]
+pub struct ConfirmationOptions {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The minimal cumulative weight of the confirmed vertex
+    #[clap(long, group = "threshold")]
+    pub weight: Option<usize>,
+
+    /// The minimal fraction of tips that approve the confirmed vertex
+    #[clap(long, group = "threshold")]
+    pub tip_fraction: Option<f64>,
+
+    /// The age (relative to the latest timestamp) after which the unconfirmed vertex is at risk
+    /// of being orphaned
+    #[clap(long, default_value_t = 10)]
+    pub max_pending_age: u32,
+}
+
+impl From<&ConfirmationOptions> for ConfirmationConfig {
+    fn from(options: &ConfirmationOptions) -> Self {
+        let threshold = match (options.weight, options.tip_fraction) {
+            (Some(weight), _) => ConfirmationThreshold::Weight(weight),
+            (None, Some(fraction)) => ConfirmationThreshold::TipFraction(fraction),
+            (None, None) => unreachable!("the threshold is required"),
+        };
+        ConfirmationConfig {
+            threshold,
+            max_pending_age: options.max_pending_age,
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct ConeOptions {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The ID of the vertex
+    #[clap(long)]
+    pub id: usize,
+
+    /// Prints the IDs of the vertices instead of the sizes of the cones
+    #[clap(long)]
+    pub ids: bool,
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct ExportOptions {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// The format of the output
+    #[clap(long, value_enum, default_value_t)]
+    pub format: ExportFormat,
+
+    /// The path to the output file. The standard output is used if not set
+    #[clap(long, short, value_hint = ValueHint::FilePath)]
+    pub output: Option<String>,
+
+    /// The format of the rows of the text output
+    #[clap(long, value_enum, default_value_t)]
+    pub output_row_format: RowFormat,
+
+    #[clap(flatten)]
+    pub dot: DotExportOptions,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// The vertices with their statistics, one per line
+    #[default]
+    Csv,
+    /// The database format
+    Text,
+    /// The Graphviz graph
+    Dot,
+    /// GraphML, which can be read back with `--input-format graphml`
+    Graphml,
+    /// Node-link JSON, which can be read back with `--input-format json`
+    Json,
+    /// The binary database, which can be read back with `--input-format binary`
+    Binary,
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+#[clap(next_help_heading = "DOT options")]
+pub struct DotExportOptions {
+    /// Adds the root depth to the labels of the vertices
+    #[clap(long)]
+    pub label_root_depth: bool,
+
+    /// Adds the number of inbound references to the labels of the vertices
+    #[clap(long)]
+    pub label_inbounds: bool,
+
+    /// Colors the tips
+    #[clap(long)]
+    pub color_tips: bool,
+
+    /// Colors the vertices unreachable from the root
+    #[clap(long)]
+    pub color_unreachable: bool,
+
+    /// Highlights the vertex with the given ID and its past cone
+    #[clap(long, value_name = "ID")]
+    pub highlight_past_cone: Option<usize>,
+}
+
+impl From<&DotExportOptions> for DotOptions {
+    fn from(options: &DotExportOptions) -> Self {
+        DotOptions {
+            label_root_depth: options.label_root_depth,
+            label_inbounds: options.label_inbounds,
+            color_tips: options.color_tips,
+            color_unreachable: options.color_unreachable,
+            highlight_past_cone: options.highlight_past_cone,
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct GenerateOptions {
+    /// The number of vertices in the database
+    #[clap(long, default_value_t = 1000)]
+    pub vertices: usize,
+
+    /// The parents of a new vertex are chosen from that many most recent vertices
+    #[clap(long, default_value_t = 10)]
+    pub window: usize,
+
+    /// The maximal difference between the timestamps of consecutive vertices
+    #[clap(long, default_value_t = 2)]
+    pub max_time_step: u32,
+
+    /// The seed of the random number generator
+    #[clap(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// The path to the output file. The standard output is used if not set
+    #[clap(long, short, value_hint = ValueHint::FilePath)]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct ConvertOptions {
+    /// The path to the text database
+    #[clap(value_parser, env, default_value = "database.txt", value_hint = ValueHint::FilePath)]
+    pub database_file_path: String,
+
+    /// The format of the rows of the text database
+    #[clap(long, value_enum, default_value_t)]
+    pub row_format: RowFormat,
+
+    /// The path to the binary database
+    #[clap(long, short, value_hint = ValueHint::FilePath)]
+    pub output: String,
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+#[clap(group(ArgGroup::new("cut").required(true)))]
+pub struct PruneOptions {
+    #[clap(flatten)]
+    pub database: DatabaseOptions,
+
+    /// Prunes the vertices older than the timestamp
+    #[clap(long, group = "cut")]
+    pub before: Option<u32>,
+
+    /// Prunes the vertices nearer to the roots than the depth
+    #[clap(long, group = "cut")]
+    pub before_depth: Option<usize>,
+
+    /// The path to the pruned database. The standard output is used if not set
+    #[clap(long, short, value_hint = ValueHint::FilePath)]
+    pub output: Option<String>,
+
+    /// The format of the rows of the pruned database
+    #[clap(long, value_enum, default_value_t)]
+    pub output_row_format: RowFormat,
+
+    /// The path to the CSV file mapping the original IDs to the new ones
+    #[clap(long, value_hint = ValueHint::FilePath)]
+    pub id_mapping: String,
+}
+
+impl From<&PruneOptions> for Cut {
+    fn from(options: &PruneOptions) -> Self {
+        match (options.before, options.before_depth) {
+            (Some(timestamp), _) => Cut::Timestamp(timestamp),
+            (None, Some(depth)) => Cut::RootDepth(depth),
+            (None, None) => unreachable!("the cut is required"),
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// The path to the database before
+    #[clap(value_parser, value_hint = ValueHint::FilePath)]
+    pub before: String,
+
+    /// The path to the database after
+    #[clap(value_parser, value_hint = ValueHint::FilePath)]
+    pub after: String,
+
+    /// The format of both databases
+    #[clap(long, value_enum, default_value_t)]
+    pub input_format: InputFormat,
+
+    /// The format of the rows of the text databases
+    #[clap(long, value_enum, default_value_t)]
+    pub row_format: RowFormat,
+
+    /// The IDs of the genesis vertices of the database before
+    #[clap(long = "before-root", value_delimiter = ',', default_value = "1")]
+    pub before_roots: Vec<usize>,
+
+    /// The IDs of the genesis vertices of the database after, e.g. the roots printed by `prune`
+    #[clap(long = "after-root", value_delimiter = ',', default_value = "1")]
+    pub after_roots: Vec<usize>,
+
+    /// How to treat the vertices of the database before that cannot be reached from its roots
+    #[clap(long, value_enum, default_value_t)]
+    pub before_unreachable: UnreachablePolicy,
+
+    /// How to treat the vertices of the database after that cannot be reached from its roots
+    #[clap(long, value_enum, default_value_t)]
+    pub after_unreachable: UnreachablePolicy,
+
+    /// How the vertices of the databases are matched
+    #[clap(long, value_enum, default_value_t)]
+    pub match_by: MatchBy,
+
+    /// The format of the differences
+    #[clap(long, value_enum, default_value_t)]
+    pub format: DiffFormat,
+}
+
+impl DiffOptions {
+    /// The options of loading the database before
+    pub fn before_database(&self) -> DatabaseOptions {
+        self.database(&self.before, &self.before_roots, self.before_unreachable)
+    }
+
+    /// The options of loading the database after
+    pub fn after_database(&self) -> DatabaseOptions {
+        self.database(&self.after, &self.after_roots, self.after_unreachable)
+    }
+
+    fn database(
+        &self,
+        path: &str,
+        roots: &[usize],
+        unreachable: UnreachablePolicy,
+    ) -> DatabaseOptions {
+        DatabaseOptions {
+            database_file_path: path.to_string(),
+            input_format: self.input_format,
+            row_format: self.row_format,
+            roots: roots.to_vec(),
+            unreachable,
+            ..Default::default()
+        }
+    }
+}
+
+/// How the vertices of the two databases are matched
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchBy {
+    /// The vertices with the same ID are matched
+    #[default]
+    Id,
+    /// The vertices with the same timestamp and the matched parents are matched
+    ContentHash,
+}
+
+impl From<MatchBy> for diff::MatchBy {
+    fn from(match_by: MatchBy) -> Self {
+        match match_by {
+            MatchBy::Id => diff::MatchBy::Id,
+            MatchBy::ContentHash => diff::MatchBy::ContentHash,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimestampValidation {
+    /// The violations are reported and the program fails
+    Strict,
+    /// The violations are reported as warnings
+    Warn,
+    /// The timestamps aren't validated
+    #[default]
+    Off,
 }
@@ -1,68 +1,502 @@
 use std::{
+    borrow::Cow,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
 };
 
-use anyhow::{bail, Context};
-use itertools::Itertools;
+use flate2::bufread::MultiGzDecoder;
 use log::debug;
 
-use crate::vertex::Vertex;
+use crate::{
+    error::{LedgerError, ParseError, RowError},
+    vertex::{RowFormat, Vertex},
+};
+
+/// The capacity reserved up front for the vertices declared in the header, which can't be
+/// trusted before the rows are read
+pub const MAX_PREALLOCATED: usize = 1 << 24;
+
+/// The path of the standard input
+pub const STDIN_PATH: &str = "-";
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The compression of the database, detected by the magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
 
-pub fn load_vertices_from_database(database_file: &str) -> Result<Vec<Vertex>, anyhow::Error> {
-    let (db_entries, expected_entries) = load_data_from_file(database_file)?;
-    let mut vertices = vec![Vertex {
-        ..Default::default()
-    }];
-    let vertices_from_db: Vec<Vertex> =
-        db_entries.map(convert_maybe_string_to_node).try_collect()?;
-    if vertices_from_db.len() != expected_entries {
-        bail!(
-            "The number vertices ({}) isn't equal to the number declared: {expected_entries}",
-            vertices_from_db.len()
-        )
+impl Compression {
+    /// Detects the compression by the first bytes of the data
+    pub fn detect(bytes: &[u8]) -> Option<Compression> {
+        if bytes.starts_with(&GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if bytes.starts_with(&ZSTD_MAGIC) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn load_vertices_from_database(database_file: &str) -> Result<Vec<Vertex>, LedgerError> {
+    read_vertices(open_database(database_file)?)
+}
+
+/// Opens the database file, or the standard input if the path is [`STDIN_PATH`]. The gzip and
+/// zstd compressed data is decompressed transparently.
+pub fn open_database(database_file: &str) -> Result<Box<dyn BufRead>, LedgerError> {
+    if database_file == STDIN_PATH {
+        decompress(io::stdin().lock())
+    } else {
+        decompress(BufReader::new(File::open(database_file)?))
+    }
+}
+
+/// Wraps the reader in the decoder if the data is compressed, otherwise returns it as it is
+pub fn decompress<'a>(mut reader: impl BufRead + 'a) -> Result<Box<dyn BufRead + 'a>, LedgerError> {
+    // a pipe can return fewer bytes than the magic at once, so they're read until the longest
+    // magic or the end of the data and put back in front of the rest
+    let mut magic = Vec::with_capacity(ZSTD_MAGIC.len());
+    while magic.len() < ZSTD_MAGIC.len() {
+        let buffer = match reader.fill_buf() {
+            Ok(buffer) => buffer,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        if buffer.is_empty() {
+            break;
+        }
+        let length = buffer.len().min(ZSTD_MAGIC.len() - magic.len());
+        magic.extend_from_slice(&buffer[..length]);
+        reader.consume(length);
+    }
+    let compression = Compression::detect(&magic);
+    let reader = io::Cursor::new(magic).chain(reader);
+    Ok(match compression {
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    })
+}
+
+/// Reads the vertices in the database format. Like [`load_vertices_from_database`], the result
+/// starts with the root, which isn't stored in the database.
+pub fn read_vertices(reader: impl BufRead) -> Result<Vec<Vertex>, LedgerError> {
+    read_vertices_with_format(reader, RowFormat::TwoParents)
+}
+
+/// Like [`read_vertices`], but with the rows in the given format
+pub fn read_vertices_with_format(
+    reader: impl BufRead,
+    format: RowFormat,
+) -> Result<Vec<Vertex>, LedgerError> {
+    let rows = VertexRows::new(reader)?.with_row_format(format);
+    let expected_entries = rows.declared_vertices();
+    let mut vertices = Vec::with_capacity(expected_entries.min(MAX_PREALLOCATED) + 1);
+    vertices.push(Vertex::default());
+    for vertex in rows {
+        vertices.push(vertex?);
+    }
+    if vertices.len() - 1 != expected_entries {
+        return Err(LedgerError::HeaderMismatch {
+            declared: expected_entries,
+            actual: vertices.len() - 1,
+        });
     }
-    vertices.extend(vertices_from_db);
 
     Ok(vertices)
 }
 
-fn convert_maybe_string_to_node(
-    (line_number, result): (usize, Result<String, std::io::Error>),
-) -> Result<Vertex, anyhow::Error> {
-    match result {
-        Ok(string_line) => Vertex::from_str(string_line, line_number + 1),
-        Err(err) => Err(anyhow::Error::from(err)),
-    }
-}
-
-pub fn load_data_from_file(
-    filename: impl AsRef<str>,
-) -> Result<
-    (
-        impl Iterator<Item = (usize, Result<String, std::io::Error>)>,
-        usize,
-    ),
-    anyhow::Error,
-> {
-    let file = File::open(filename.as_ref())?;
-    let reader = BufReader::new(file);
-    let mut lines_reader = reader.lines().enumerate();
-    let number_of_entries = get_number_of_nodes(&mut lines_reader)?;
-
-    Ok((lines_reader, number_of_entries))
-}
-
-fn get_number_of_nodes(
-    buffer: &mut impl Iterator<Item = (usize, Result<String, std::io::Error>)>,
-) -> Result<usize, anyhow::Error> {
-    let first_line = buffer
-        .next()
-        .context("end of file")?
-        .1
-        .context("unable to get the first line")?;
-    let number_of_nodes: usize = first_line.parse()?;
-
-    debug!("Extracted number of nodes in graph: {number_of_nodes}");
-    Ok(number_of_nodes)
+/// Opens the database like [`open_database`] and streams its vertices
+pub fn load_vertex_rows(database_file: &str) -> Result<VertexRows<Box<dyn BufRead>>, LedgerError> {
+    VertexRows::new(open_database(database_file)?)
+}
+
+/// The iterator over the vertices of the database, which doesn't collect them, so the ledgers
+/// bigger than memory can be processed. The rows are read into one reused buffer, so there's
+/// no heap allocation per row. The root isn't stored in the database, so the first vertex has
+/// ID 2.
+///
+/// The iterator doesn't check the number of the vertices declared in the header.
+pub struct VertexRows<R> {
+    reader: R,
+    line: Vec<u8>,
+    line_number: usize,
+    declared: usize,
+    format: RowFormat,
+}
+
+impl<R: BufRead> VertexRows<R> {
+    /// Reads the header of the database
+    pub fn new(mut reader: R) -> Result<Self, LedgerError> {
+        let mut line = vec![];
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(LedgerError::MissingHeader);
+        }
+        let header = line_as_str(&line)?;
+        let declared = header
+            .parse()
+            .map_err(|source| LedgerError::InvalidHeader {
+                header: header.to_string(),
+                source,
+            })?;
+
+        debug!("Extracted number of nodes in graph: {declared}");
+        Ok(VertexRows {
+            reader,
+            line,
+            line_number: 1,
+            declared,
+            format: Default::default(),
+        })
+    }
+
+    /// Streams the vertices of the reader positioned after the header
+    pub(crate) fn after_header(reader: R, declared: usize) -> Self {
+        VertexRows {
+            reader,
+            line: vec![],
+            line_number: 1,
+            declared,
+            format: Default::default(),
+        }
+    }
+
+    /// Sets the format of the rows, [`RowFormat::TwoParents`] by default
+    pub fn with_row_format(mut self, format: RowFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The number of vertices declared in the header
+    pub fn declared_vertices(&self) -> usize {
+        self.declared
+    }
+
+    /// The last row read, e.g. to show where the row is malformed
+    pub fn last_line(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(strip_line_ending(&self.line))
+    }
+}
+
+impl<R: BufRead> Iterator for VertexRows<R> {
+    type Item = Result<Vertex, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.line.clear();
+        match self.reader.read_until(b'\n', &mut self.line) {
+            Ok(0) => None,
+            Ok(_) => {
+                // the line number is also the ID of the vertex
+                self.line_number += 1;
+                Some(match std::str::from_utf8(strip_line_ending(&self.line)) {
+                    Ok(line) => Vertex::parse_row_with_format(line, self.line_number, self.format),
+                    Err(err) => Err(ParseError::MalformedRow {
+                        line: self.line_number,
+                        column: 1,
+                        kind: RowError::InvalidUtf8(err),
+                    }),
+                })
+            }
+            Err(err) => Some(Err(err.into())),
+        }
+    }
+}
+
+pub(crate) fn line_as_str(line: &[u8]) -> Result<&str, io::Error> {
+    std::str::from_utf8(strip_line_ending(line))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Strips the line ending like [`BufRead::lines`]
+fn strip_line_ending(line: &[u8]) -> &[u8] {
+    match line.strip_suffix(b"\n") {
+        Some(line) => line.strip_suffix(b"\r").unwrap_or(line),
+        None => line,
+    }
+}
+
+/// Writes the vertices to the database file. It's the counterpart of
+/// [`load_vertices_from_database`]: the first vertex is the root, which isn't stored.
+pub fn write_vertices_to_database(
+    database_file: &str,
+    vertices: &[Vertex],
+) -> Result<(), LedgerError> {
+    let mut writer = BufWriter::new(File::create(database_file)?);
+    write_vertices(&mut writer, vertices)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the vertices in the database format. The missing edge is written as the reference
+/// to the vertex itself, which [`Vertex::from_str`] reads back as the missing edge. For the same
+/// reason the vertex cannot reference itself, and the root must have no data.
+pub fn write_vertices(writer: impl Write, vertices: &[Vertex]) -> Result<(), LedgerError> {
+    write_vertices_with_format(writer, vertices, RowFormat::TwoParents)
+}
+
+/// Like [`write_vertices`], but with the rows in the given format. Only the
+/// [`RowFormat::ParentCount`] rows can store more than two parents.
+pub fn write_vertices_with_format(
+    mut writer: impl Write,
+    vertices: &[Vertex],
+    format: RowFormat,
+) -> Result<(), LedgerError> {
+    let (root, stored) = vertices.split_first().ok_or(LedgerError::EmptyGraph)?;
+    if *root != Vertex::default() {
+        return Err(LedgerError::UnrepresentableVertex {
+            id: 1,
+            reason:
+                "the root isn't stored in the database, so it must have no edges and timestamp 0",
+        });
+    }
+
+    writeln!(writer, "{}", stored.len())?;
+    for (idx, vertex) in stored.iter().enumerate() {
+        // the root has ID 1, so the first stored vertex has ID 2
+        let id = idx + 2;
+        if vertex.parents.contains(&id) {
+            return Err(LedgerError::UnrepresentableVertex {
+                id,
+                reason: "the self-reference is the missing edge in the database",
+            });
+        }
+        match format {
+            RowFormat::TwoParents => {
+                if vertex.parents.len() > 2 {
+                    return Err(LedgerError::UnrepresentableVertex {
+                        id,
+                        reason:
+                            "only the rows with the parent count can store more than two parents",
+                    });
+                }
+                let parent = |idx| vertex.parents.get(idx).copied().unwrap_or(id);
+                writeln!(writer, "{} {} {}", parent(0), parent(1), vertex.timestamp)?;
+            }
+            RowFormat::ParentCount => {
+                write!(writer, "{}", vertex.parents.len())?;
+                for parent in vertex.parents.iter() {
+                    write!(writer, " {parent}")?;
+                }
+                writeln!(writer, " {}", vertex.timestamp)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{
+        test_util::vertex_at,
+        vertex::{Parents, MAX_PARENTS},
+    };
+
+    /// Any vertices with up to `max_parents` parents that can be stored in the database, with
+    /// IDs that may point anywhere
+    fn vertices(max_parents: usize) -> impl Strategy<Value = Vec<Vertex>> {
+        (0usize..50)
+            .prop_flat_map(move |count| {
+                proptest::collection::vec(
+                    (
+                        proptest::collection::vec(1..=count + 1, 0..=max_parents),
+                        any::<u32>(),
+                    ),
+                    count,
+                )
+            })
+            .prop_map(|rows| {
+                let mut vertices = vec![Vertex::default()];
+                for (idx, (mut parents, timestamp)) in rows.into_iter().enumerate() {
+                    let id = idx + 2;
+                    parents.retain(|parent| *parent != id);
+                    vertices.push(vertex_at(&parents, timestamp));
+                }
+                vertices
+            })
+    }
+
+    fn write_to_string(vertices: &[Vertex], format: RowFormat) -> String {
+        let mut output = vec![];
+        write_vertices_with_format(&mut output, vertices, format).expect("writing to vector");
+        String::from_utf8(output).expect("valid UTF-8")
+    }
+
+    fn assert_read_write_read_is_identity(
+        vertices: &[Vertex],
+        format: RowFormat,
+    ) -> Result<(), TestCaseError> {
+        let text = write_to_string(vertices, format);
+        let read = read_vertices_with_format(text.as_bytes(), format).expect("valid database");
+        prop_assert_eq!(vertices, &read);
+
+        let written_again = write_to_string(&read, format);
+        prop_assert_eq!(text, written_again);
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_read_write_read_is_identity(vertices in vertices(2)) {
+            assert_read_write_read_is_identity(&vertices, RowFormat::TwoParents)?;
+        }
+
+        #[test]
+        fn test_read_write_read_parent_count_is_identity(vertices in vertices(MAX_PARENTS)) {
+            assert_read_write_read_is_identity(&vertices, RowFormat::ParentCount)?;
+        }
+    }
+
+    fn compressed_database(compression: Compression) -> Vec<u8> {
+        let text = "3\n1 1 0\n2 3 1\n4 4 2\n";
+        match compression {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder
+                    .write_all(text.as_bytes())
+                    .expect("writing to vector");
+                encoder.finish().expect("writing to vector")
+            }
+            Compression::Zstd => zstd::encode_all(text.as_bytes(), 0).expect("writing to vector"),
+        }
+    }
+
+    #[test]
+    fn test_read_compressed_vertices() {
+        let expected = read_vertices("3\n1 1 0\n2 3 1\n4 4 2\n".as_bytes()).expect("valid");
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let data = compressed_database(compression);
+            assert_eq!(Some(compression), Compression::detect(&data));
+            let reader = decompress(data.as_slice()).expect("valid compressed data");
+            assert_eq!(expected, read_vertices(reader).expect("valid database"));
+        }
+    }
+
+    #[test]
+    fn test_decompress_one_byte_at_a_time() {
+        let expected = read_vertices("3\n1 1 0\n2 3 1\n4 4 2\n".as_bytes()).expect("valid");
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let data = compressed_database(compression);
+            let reader = decompress(BufReader::with_capacity(1, data.as_slice()))
+                .expect("valid compressed data");
+            assert_eq!(expected, read_vertices(reader).expect("valid database"));
+        }
+        for text in ["3\n1 1 0\n2 3 1\n4 4 2\n", "3"] {
+            let mut reader = decompress(BufReader::with_capacity(1, text.as_bytes()))
+                .expect("reading from slice");
+            let mut read = String::new();
+            reader
+                .read_to_string(&mut read)
+                .expect("reading from slice");
+            assert_eq!(text, read);
+        }
+    }
+
+    #[test]
+    fn test_decompress_plain_text() {
+        let text = "1\n1 1 0\n";
+        assert_eq!(None, Compression::detect(text.as_bytes()));
+        let reader = decompress(text.as_bytes()).expect("reading from slice");
+        assert_eq!(2, read_vertices(reader).expect("valid database").len());
+    }
+
+    #[test]
+    fn test_vertex_rows() {
+        let rows = VertexRows::new("3\r\n1 1 0\r\n2 3 1\nx 4 2".as_bytes()).expect("valid header");
+        assert_eq!(3, rows.declared_vertices());
+        let rows: Vec<_> = rows.collect();
+        assert_eq!(3, rows.len());
+        assert_eq!(
+            &Vertex {
+                parents: Parents::from([2]),
+                timestamp: 1
+            },
+            rows[1].as_ref().expect("valid row")
+        );
+        assert!(
+            matches!(
+                rows[2],
+                Err(ParseError::MalformedRow {
+                    line: 4,
+                    column: 1,
+                    kind: RowError::InvalidLeftId(_)
+                })
+            ),
+            "{:?}",
+            rows[2]
+        );
+    }
+
+    #[test]
+    fn test_vertex_rows_invalid_utf8() {
+        let rows: Vec<_> = VertexRows::new(&b"2\n\xff 1 0\n1 1 1\n"[..])
+            .expect("valid header")
+            .collect();
+        assert!(
+            matches!(
+                rows[0],
+                Err(ParseError::MalformedRow {
+                    line: 2,
+                    column: 1,
+                    kind: RowError::InvalidUtf8(_)
+                })
+            ),
+            "{:?}",
+            rows[0]
+        );
+        assert!(rows[1].is_ok());
+    }
+
+    #[test]
+    fn test_write_vertices_self_reference() {
+        let text = "3\n1 1 0\n2 3 1\n4 4 2\n";
+        let vertices = read_vertices(text.as_bytes()).expect("valid database");
+        assert_eq!(Parents::from([2]), vertices[2].parents);
+        assert!(vertices[3].parents.is_empty());
+        assert_eq!(text, write_to_string(&vertices, RowFormat::TwoParents));
+    }
+
+    #[test]
+    fn test_write_vertices_unrepresentable() {
+        let vertices = vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([2]),
+                timestamp: 0,
+            },
+        ];
+        let err = write_vertices(vec![], &vertices).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::UnrepresentableVertex { id: 2, .. }),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_write_vertices_too_many_parents() {
+        let vertices = vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([1, 1, 1]),
+                timestamp: 0,
+            },
+        ];
+        let err = write_vertices(vec![], &vertices).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::UnrepresentableVertex { id: 2, .. }),
+            "{err}"
+        );
+        assert_eq!(
+            "1\n3 1 1 1 0\n",
+            write_to_string(&vertices, RowFormat::ParentCount)
+        );
+    }
 }
@@ -0,0 +1,352 @@
+//! The lenient parsing of the database, which doesn't stop at the first error, but collects all
+//! of them and renders them like a compiler.
+
+use std::{
+    io::{self, BufRead, Write},
+    num::ParseIntError,
+};
+
+use crate::{
+    database::{line_as_str, VertexRows},
+    error::{LedgerError, ParseError},
+    vertex::{column_of, RowFormat, Vertex},
+};
+
+type Id = usize;
+
+/// The location of the problem in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// 1-based line, which is also the ID of the vertex
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+    /// The number of characters
+    pub length: usize,
+}
+
+/// The problem found in the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// `None` if the problem is about the whole database
+    pub span: Option<Span>,
+    /// The line of the span
+    pub source_line: Option<String>,
+}
+
+impl Diagnostic {
+    /// The `column` is 1-based and counted in bytes, like in [`ParseError::MalformedRow`]
+    fn at(message: String, source_line: &str, line: usize, column: usize) -> Diagnostic {
+        Diagnostic {
+            message,
+            span: Some(Span {
+                line,
+                column: char_column(source_line, column),
+                length: token_length(source_line, column).max(1),
+            }),
+            source_line: Some(source_line.to_string()),
+        }
+    }
+}
+
+/// The reference that may point outside the database, which is known only after all the rows
+/// are read. The valid references point to the earlier vertices, so there are few candidates.
+struct Candidate {
+    id: Id,
+    line: usize,
+    column: usize,
+    source_line: String,
+}
+
+/// Parses the whole database and collects all the problems: malformed rows, the number of
+/// vertices different from the header and references to non-existing vertices. Only the I/O
+/// errors stop the parsing.
+///
+/// Like [`crate::database::read_vertices`], the result starts with the root. The malformed rows
+/// are replaced with the default vertex, so the IDs of the following vertices don't change.
+pub fn read_vertices_lenient(
+    mut reader: impl BufRead,
+    format: RowFormat,
+) -> Result<(Vec<Vertex>, Vec<Diagnostic>), LedgerError> {
+    let mut diagnostics = vec![];
+
+    let mut header = vec![];
+    if reader.read_until(b'\n', &mut header)? == 0 {
+        diagnostics.push(Diagnostic {
+            message: LedgerError::MissingHeader.to_string(),
+            span: None,
+            source_line: None,
+        });
+        return Ok((vec![Vertex::default()], diagnostics));
+    }
+    let parsed = line_as_str(&header)
+        .map_err(|err| err.to_string())
+        .and_then(|header| header.parse().map_err(|err: ParseIntError| err.to_string()));
+    let header = String::from_utf8_lossy(&header).trim_end().to_string();
+    let declared = match parsed {
+        Ok(declared) => Some(declared),
+        Err(err) => {
+            let message = format!("unable to parse the header: {err}");
+            diagnostics.push(Diagnostic::at(message, &header, 1, 1));
+            None
+        }
+    };
+
+    let mut vertices = vec![Vertex::default()];
+    let mut candidates = vec![];
+    let mut rows =
+        VertexRows::after_header(reader, declared.unwrap_or_default()).with_row_format(format);
+    while let Some(row) = rows.next() {
+        let id = vertices.len() + 1;
+        match row {
+            Ok(vertex) => {
+                let line = rows.last_line();
+                for chunk in format.parent_items(&line) {
+                    let parent: Id = chunk.parse().expect("parsed");
+                    if parent == 0 || parent > id {
+                        candidates.push(Candidate {
+                            id: parent,
+                            line: id,
+                            column: column_of(&line, chunk),
+                            source_line: line.to_string(),
+                        });
+                    }
+                }
+                vertices.push(vertex);
+            }
+            Err(ParseError::MalformedRow { line, column, kind }) => {
+                let message = kind.to_string();
+                diagnostics.push(Diagnostic::at(message, &rows.last_line(), line, column));
+                vertices.push(Vertex::default());
+            }
+            Err(ParseError::Io(err)) => return Err(err.into()),
+        }
+    }
+
+    let actual = vertices.len() - 1;
+    if let Some(declared) = declared.filter(|declared| *declared != actual) {
+        let message = LedgerError::HeaderMismatch { declared, actual }.to_string();
+        diagnostics.push(Diagnostic::at(message, &header, 1, 1));
+    }
+
+    let max_id = vertices.len();
+    for candidate in candidates {
+        if candidate.id == 0 || candidate.id > max_id {
+            let message = LedgerError::InvalidVertexId {
+                id: candidate.id,
+                max_id,
+            }
+            .to_string();
+            diagnostics.push(Diagnostic::at(
+                message,
+                &candidate.source_line,
+                candidate.line,
+                candidate.column,
+            ));
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+
+    Ok((vertices, diagnostics))
+}
+
+/// Renders the diagnostics like a compiler, followed by the summary:
+///
+/// ```text
+/// error: unable to parse the left ID: invalid digit found in string
+///  --> database.txt:4:1
+///   |
+/// 4 | x 4 2
+///   | ^
+/// ```
+pub fn render_diagnostics(
+    diagnostics: &[Diagnostic],
+    file_name: &str,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    for diagnostic in diagnostics {
+        writeln!(writer, "error: {}", diagnostic.message)?;
+        let Some(span) = diagnostic.span else {
+            writeln!(writer, " --> {file_name}")?;
+            writeln!(writer)?;
+            continue;
+        };
+        let gutter = " ".repeat(span.line.to_string().len());
+        writeln!(
+            writer,
+            "{gutter}--> {file_name}:{}:{}",
+            span.line, span.column
+        )?;
+        if let Some(source_line) = &diagnostic.source_line {
+            writeln!(writer, "{gutter} |")?;
+            writeln!(writer, "{} | {source_line}", span.line)?;
+            writeln!(
+                writer,
+                "{gutter} | {}{}",
+                " ".repeat(span.column - 1),
+                "^".repeat(span.length)
+            )?;
+        }
+        writeln!(writer)?;
+    }
+
+    match diagnostics.len() {
+        0 => Ok(()),
+        1 => writeln!(writer, "error: could not load `{file_name}` due to 1 error"),
+        count => writeln!(
+            writer,
+            "error: could not load `{file_name}` due to {count} errors"
+        ),
+    }
+}
+
+/// Converts the 1-based byte column to the 1-based character column, so the caret is placed
+/// under the token also after the multi-byte characters
+fn char_column(line: &str, column: usize) -> usize {
+    line.get(..column - 1)
+        .map_or(column, |before| before.chars().count() + 1)
+}
+
+/// The number of characters of the token starting at the 1-based byte column, 0 past the end
+/// of the line
+fn token_length(line: &str, column: usize) -> usize {
+    line.get(column - 1..)
+        .and_then(|rest| rest.split_ascii_whitespace().next())
+        .map_or(0, |token| token.chars().count())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn diagnostics(database: &str) -> Vec<Diagnostic> {
+        read_vertices_lenient(database.as_bytes(), RowFormat::TwoParents)
+            .expect("reading from slice")
+            .1
+    }
+
+    #[test]
+    fn test_read_vertices_lenient_valid() {
+        let (vertices, diagnostics) =
+            read_vertices_lenient("2\n1 1 0\n2 1 1\n".as_bytes(), RowFormat::TwoParents)
+                .expect("reading from slice");
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!(3, vertices.len());
+    }
+
+    #[test]
+    fn test_read_vertices_lenient_collects_all_errors() {
+        let diagnostics = diagnostics("5\n1 1 0\nx 2 1\n1 9 2\n1 1\n1 1 1 1\n1 0 4\n");
+        let spans: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.span.expect("located"))
+            .map(|span| (span.line, span.column, span.length))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, 1, 1),
+                (3, 1, 1),
+                (4, 3, 1),
+                (5, 4, 1),
+                (6, 7, 1),
+                (7, 3, 1)
+            ],
+            spans
+        );
+        assert!(diagnostics[0].message.contains("declared: 5"));
+        assert!(diagnostics[2]
+            .message
+            .contains("vertex with ID 9 doesn't exist"));
+        assert!(diagnostics[5].message.contains("ID 0"));
+    }
+
+    #[test]
+    fn test_read_vertices_lenient_parent_count() {
+        let database = "3\n1 1 0\n3 1 2 9 1\n2 1 x 2\n";
+        let (vertices, diagnostics) =
+            read_vertices_lenient(database.as_bytes(), RowFormat::ParentCount)
+                .expect("reading from slice");
+        let spans: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.span.expect("located"))
+            .map(|span| (span.line, span.column))
+            .collect();
+        assert_eq!(vec![(3, 7), (4, 5)], spans);
+        assert!(diagnostics[0]
+            .message
+            .contains("vertex with ID 9 doesn't exist"));
+        assert_eq!(4, vertices.len());
+    }
+
+    #[test]
+    fn test_read_vertices_lenient_invalid_utf8() {
+        let (vertices, diagnostics) =
+            read_vertices_lenient(&b"3\n1 1 0\n\xff 1 1\n2 x 2\n"[..], RowFormat::TwoParents)
+                .expect("reading from slice");
+        let spans: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.span.expect("located"))
+            .map(|span| (span.line, span.column))
+            .collect();
+        assert_eq!(vec![(3, 1), (4, 3)], spans);
+        assert!(diagnostics[0].message.contains("UTF-8"), "{diagnostics:?}");
+        assert_eq!(4, vertices.len());
+
+        let (_, diagnostics) = read_vertices_lenient(&b"\xff\n1 1 0\n"[..], RowFormat::TwoParents)
+            .expect("reading from slice");
+        assert_eq!(1, diagnostics.len(), "{diagnostics:?}");
+        assert!(diagnostics[0].message.contains("utf-8"), "{diagnostics:?}");
+    }
+
+    #[test]
+    fn test_read_vertices_lenient_invalid_header() {
+        let diagnostics = diagnostics("abc\n1 1 0\n");
+        assert_eq!(1, diagnostics.len(), "{diagnostics:?}");
+        assert!(diagnostics[0].message.contains("header"));
+    }
+
+    #[test]
+    fn test_render_diagnostics() {
+        let mut output = vec![];
+        render_diagnostics(&diagnostics("1\nx 1 0\n"), "database.txt", &mut output)
+            .expect("writing to vector");
+        assert_eq!(
+            "error: unable to parse the left ID: invalid digit found in string\n \
+            --> database.txt:2:1\n  \
+              |\n\
+            2 | x 1 0\n  \
+              | ^\n\
+            \n\
+            error: could not load `database.txt` due to 1 error\n",
+            String::from_utf8(output).expect("valid UTF-8")
+        );
+    }
+
+    #[test]
+    fn test_render_diagnostics_multi_byte_characters() {
+        let mut output = vec![];
+        render_diagnostics(
+            &diagnostics("2\n1 1 éé\n1 é 0 9\n"),
+            "database.txt",
+            &mut output,
+        )
+        .expect("writing to vector");
+        assert_eq!(
+            "error: unable to parse the timestamp: invalid digit found in string\n \
+            --> database.txt:2:5\n  \
+              |\n\
+            2 | 1 1 éé\n  \
+              |     ^^\n\
+            \n\
+            error: the row has too many items\n \
+            --> database.txt:3:7\n  \
+              |\n\
+            3 | 1 é 0 9\n  \
+              |       ^\n\
+            \n\
+            error: could not load `database.txt` due to 2 errors\n",
+            String::from_utf8(output).expect("valid UTF-8")
+        );
+    }
+}
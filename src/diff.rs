@@ -0,0 +1,362 @@
+//! Comparison of two ledgers, e.g. the exports from two nodes. The vertices are matched by ID or
+//! by the content hash, which is the same for the vertices with the same timestamp and the same
+//! history, regardless of their IDs.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::{
+    error::LedgerError,
+    graph::{Graph, GraphStats},
+};
+
+type Id = usize;
+
+/// How the vertices of the two graphs are matched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatchBy {
+    /// The vertices with the same ID are matched
+    #[default]
+    Id,
+    /// The vertices with the same timestamp and the matched parents are matched. The identical
+    /// vertices are matched in the order of their IDs.
+    ContentHash,
+}
+
+/// The IDs of the matched vertex in both graphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Matched {
+    pub before: Id,
+    pub after: Id,
+}
+
+/// The edge from the vertex to its parent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct Edge {
+    pub approver: Id,
+    pub parent: Id,
+}
+
+/// The statistic in both graphs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatChange {
+    pub name: &'static str,
+    pub before: f64,
+    pub after: f64,
+}
+
+/// The differences between the graph before and the graph after. The IDs of the removed vertices
+/// and edges are the ones in the graph before, and the added ones are in the graph after.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Diff {
+    pub added: Vec<Id>,
+    pub removed: Vec<Id>,
+    /// The matched vertices with different timestamps or parents. With [`MatchBy::ContentHash`]
+    /// the matched vertices have the same content, so it's empty unless the hashes collide.
+    pub changed: Vec<Matched>,
+    /// The matched vertices with different IDs. It's always empty with [`MatchBy::Id`].
+    pub moved: Vec<Matched>,
+    pub added_edges: Vec<Edge>,
+    pub removed_edges: Vec<Edge>,
+    /// Every statistic of both graphs, including the unchanged ones
+    pub stats: Vec<StatChange>,
+}
+
+impl Diff {
+    /// Whether the graphs have the same vertices and edges
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+    }
+}
+
+/// Compares the graphs. Both must be analyzed with [`Graph::walk_and_analyze`]. Matching by the
+/// content hash fails if either graph has a cycle.
+pub fn diff(before: &Graph, after: &Graph, match_by: MatchBy) -> Result<Diff, LedgerError> {
+    let matches = match match_by {
+        MatchBy::Id => (1..=before.graph.len())
+            .map(|id| Some(id).filter(|id| *id <= after.graph.len()))
+            .collect(),
+        MatchBy::ContentHash => match_by_content_hash(before, after)?,
+    };
+    let mut matched_after = vec![false; after.graph.len()];
+    let mut diff = Diff::default();
+    for (idx, matched) in matches.iter().enumerate() {
+        let Some(matched) = *matched else {
+            diff.removed.push(idx + 1);
+            continue;
+        };
+        matched_after[matched - 1] = true;
+        let matched = Matched {
+            before: idx + 1,
+            after: matched,
+        };
+        let (vertex, other) = (
+            &before.graph[idx].vertex,
+            &after.graph[matched.after - 1].vertex,
+        );
+        let parents_match = vertex.parents.len() == other.parents.len()
+            && vertex
+                .parents
+                .iter()
+                .zip(other.parents.iter())
+                .all(|(parent, other)| matches[parent - 1] == Some(*other));
+        if !parents_match || vertex.timestamp != other.timestamp {
+            diff.changed.push(matched);
+        }
+        if matched.before != matched.after {
+            diff.moved.push(matched);
+        }
+    }
+    diff.added = (1..=after.graph.len())
+        .filter(|id| !matched_after[id - 1])
+        .collect();
+
+    let edges_after: HashSet<Edge> = edges(after).collect();
+    let mut mapped_before = HashSet::new();
+    for edge in edges(before) {
+        let mapped = matches[edge.approver - 1]
+            .zip(matches[edge.parent - 1])
+            .map(|(approver, parent)| Edge { approver, parent });
+        match mapped {
+            Some(mapped) if edges_after.contains(&mapped) => {
+                mapped_before.insert(mapped);
+            }
+            _ => diff.removed_edges.push(edge),
+        }
+    }
+    diff.added_edges = edges(after)
+        .filter(|edge| !mapped_before.contains(edge))
+        .collect();
+
+    diff.stats = stat_changes(&before.stats(), &after.stats());
+    Ok(diff)
+}
+
+/// The distinct edges of the graph, ordered by the approver
+fn edges(graph: &Graph) -> impl Iterator<Item = Edge> + '_ {
+    graph.graph.iter().enumerate().flat_map(|(idx, vertex)| {
+        let parents = &vertex.vertex.parents;
+        parents
+            .iter()
+            .enumerate()
+            .filter(|(position, parent)| !parents[..*position].contains(parent))
+            .map(move |(_, parent)| Edge {
+                approver: idx + 1,
+                parent: *parent,
+            })
+    })
+}
+
+/// Matches every vertex before to the vertex after with the same hash
+fn match_by_content_hash(before: &Graph, after: &Graph) -> Result<Vec<Option<Id>>, LedgerError> {
+    let mut unmatched: HashMap<u64, Vec<Id>> = HashMap::new();
+    for (idx, hash) in content_hashes(after)?.into_iter().enumerate().rev() {
+        unmatched.entry(hash).or_default().push(idx + 1);
+    }
+    Ok(content_hashes(before)?
+        .into_iter()
+        .map(|hash| unmatched.get_mut(&hash).and_then(Vec::pop))
+        .collect())
+}
+
+/// Hashes the timestamp of every vertex together with the hashes of its parents. The vertices
+/// are hashed in the topological order, so the parents are hashed first even if they're later
+/// in the database. The hashes of the graphs compared can come from different builds, so the
+/// algorithm and the byte order are fixed.
+fn content_hashes(graph: &Graph) -> Result<Vec<u64>, LedgerError> {
+    let mut hashes = vec![0u64; graph.graph.len()];
+    for id in graph.topological_order()? {
+        let vertex = &graph.graph[id - 1].vertex;
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, &vertex.timestamp.to_le_bytes());
+        for parent in vertex.parents.iter().filter(|parent| **parent != id) {
+            hash = fnv1a(hash, &hashes[parent - 1].to_le_bytes());
+        }
+        hashes[id - 1] = hash;
+    }
+    Ok(hashes)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continues the 64-bit FNV-1a hash with the bytes
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn stat_changes(before: &GraphStats, after: &GraphStats) -> Vec<StatChange> {
+    let change = |name, before, after| StatChange {
+        name,
+        before,
+        after,
+    };
+    vec![
+        change("vertices", before.vertices as f64, after.vertices as f64),
+        change(
+            "unreachable_vertices",
+            before.unreachable_vertices as f64,
+            after.unreachable_vertices as f64,
+        ),
+        change(
+            "avg_root_depth_per_node",
+            before.avg_root_depth_per_node,
+            after.avg_root_depth_per_node,
+        ),
+        change(
+            "avg_nodes_per_root_depth",
+            before.avg_nodes_per_root_depth,
+            after.avg_nodes_per_root_depth,
+        ),
+        change(
+            "avg_inbound_ref_per_node",
+            before.avg_inbound_ref_per_node,
+            after.avg_inbound_ref_per_node,
+        ),
+        change("tips", before.tips as f64, after.tips as f64),
+        change("tip_ratio", before.tip_ratio, after.tip_ratio),
+        change("avg_tip_age", before.avg_tip_age, after.avg_tip_age),
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        graph::UnreachablePolicy,
+        test_util::graph,
+        vertex::{Parents, Vertex},
+    };
+
+    use super::*;
+
+    type Timestamp = u32;
+
+    fn edge(approver: Id, parent: Id) -> Edge {
+        Edge { approver, parent }
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let vertices: &[(&[Id], Timestamp)] = &[(&[], 0), (&[1], 1), (&[1, 2], 2)];
+        for match_by in [MatchBy::Id, MatchBy::ContentHash] {
+            let diff = diff(&graph(vertices), &graph(vertices), match_by).expect("valid graphs");
+            assert!(diff.is_empty(), "{diff:?}");
+            assert!(diff.moved.is_empty());
+            assert!(diff.stats.iter().all(|stat| stat.before == stat.after));
+        }
+    }
+
+    #[test]
+    fn test_diff_by_id() {
+        let before = graph(&[(&[], 0), (&[1], 1), (&[1, 2], 2), (&[3], 3)]);
+        let after = graph(&[(&[], 0), (&[1], 1), (&[2], 2)]);
+        let diff = diff(&before, &after, MatchBy::Id).expect("valid graphs");
+        assert!(diff.added.is_empty());
+        assert_eq!(vec![4], diff.removed);
+        assert_eq!(
+            vec![Matched {
+                before: 3,
+                after: 3
+            }],
+            diff.changed
+        );
+        assert_eq!(vec![edge(3, 1), edge(4, 3)], diff.removed_edges);
+        assert!(diff.added_edges.is_empty());
+        assert_eq!(
+            StatChange {
+                name: "vertices",
+                before: 4.0,
+                after: 3.0
+            },
+            diff.stats[0]
+        );
+    }
+
+    #[test]
+    fn test_diff_by_content_hash() {
+        // the other node received 3 and 4 in the opposite order, then 5 was added
+        let before = graph(&[(&[], 0), (&[1], 1), (&[2], 2), (&[2], 3)]);
+        let after = graph(&[(&[], 0), (&[1], 1), (&[2], 3), (&[2], 2), (&[3, 4], 4)]);
+
+        let diff = diff(&before, &after, MatchBy::ContentHash).expect("valid graphs");
+        assert_eq!(vec![5], diff.added);
+        assert!(diff.removed.is_empty());
+        assert!(diff.changed.is_empty());
+        assert_eq!(
+            vec![
+                Matched {
+                    before: 3,
+                    after: 4
+                },
+                Matched {
+                    before: 4,
+                    after: 3
+                }
+            ],
+            diff.moved
+        );
+        assert_eq!(vec![edge(5, 3), edge(5, 4)], diff.added_edges);
+        assert!(diff.removed_edges.is_empty());
+
+        // matched by ID, both vertices have a different timestamp
+        let diff = super::diff(&before, &after, MatchBy::Id).expect("valid graphs");
+        assert_eq!(2, diff.changed.len());
+    }
+
+    #[test]
+    fn test_diff_forward_reference() {
+        // 2 approves the later vertex 3
+        let graph = graph(&[(&[], 0), (&[1, 3], 2), (&[1], 1)]);
+        let diff = diff(&graph, &graph, MatchBy::ContentHash).expect("acyclic graph");
+        assert!(diff.is_empty(), "{diff:?}");
+        assert!(diff.moved.is_empty());
+
+        let mut cyclic = Graph::new(vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([3]),
+                timestamp: 0,
+            },
+            Vertex {
+                parents: Parents::from([2]),
+                timestamp: 0,
+            },
+        ])
+        .with_unreachable_policy(UnreachablePolicy::Exclude);
+        cyclic.walk_and_analyze().expect("the cycle is excluded");
+        let err = super::diff(&cyclic, &cyclic, MatchBy::ContentHash).expect_err("cyclic graph");
+        assert!(
+            matches!(err, LedgerError::CyclicGraph(ref ids) if ids == &[2, 3]),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_content_hashes_are_stable() {
+        let graph = graph(&[(&[], 0), (&[1], 1), (&[1, 2], 2)]);
+        // the hashes must be the same in every build, as they're compared across the nodes
+        assert_eq!(
+            vec![
+                5558979605539197941,
+                15084595028173021806,
+                16496963333253649423
+            ],
+            content_hashes(&graph).expect("acyclic graph")
+        );
+    }
+
+    #[test]
+    fn test_diff_identical_siblings() {
+        let before = graph(&[(&[], 0), (&[1], 1), (&[1], 1)]);
+        let after = graph(&[(&[], 0), (&[1], 1), (&[1], 1), (&[1], 1)]);
+        let diff = diff(&before, &after, MatchBy::ContentHash).expect("valid graphs");
+        assert_eq!(vec![4], diff.added);
+        assert!(diff.moved.is_empty());
+    }
+}
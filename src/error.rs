@@ -0,0 +1,127 @@
+use std::{num::ParseIntError, str::Utf8Error};
+
+use thiserror::Error;
+
+use crate::vertex::MAX_PARENTS;
+
+type Id = usize;
+
+/// Errors returned by the ledger library
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("the database has no header with the number of vertices")]
+    MissingHeader,
+
+    #[error("unable to parse the header '{header}': {source}")]
+    InvalidHeader {
+        header: String,
+        source: ParseIntError,
+    },
+
+    #[error("The number vertices ({actual}) isn't equal to the number declared: {declared}")]
+    HeaderMismatch { declared: usize, actual: usize },
+
+    #[error("line {line}, column {column}: {kind}")]
+    MalformedRow {
+        line: usize,
+        column: usize,
+        kind: RowError,
+    },
+
+    #[error("{}", invalid_vertex_id_message(*.id, *.max_id))]
+    InvalidVertexId { id: Id, max_id: Id },
+
+    #[error("the graph cannot be empty")]
+    EmptyGraph,
+
+    #[error("the graph must have at least one root")]
+    NoRoots,
+
+    #[error("vertices unreachable from the root: {0:?}")]
+    UnreachableVertices(Vec<Id>),
+
+    #[error("vertices that are part of or approve a cycle: {0:?}")]
+    CyclicGraph(Vec<Id>),
+
+    #[error("vertex with ID {id} cannot be written to the database: {reason}")]
+    UnrepresentableVertex { id: Id, reason: &'static str },
+
+    #[error("invalid {format} document: {reason}")]
+    InvalidDocument {
+        format: &'static str,
+        reason: String,
+    },
+
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid XML: {0}")]
+    Xml(#[from] quick_xml::Error),
+}
+
+/// Errors returned while streaming the database rows
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("line {line}, column {column}: {kind}")]
+    MalformedRow {
+        line: usize,
+        column: usize,
+        kind: RowError,
+    },
+}
+
+impl From<ParseError> for LedgerError {
+    fn from(err: ParseError) -> Self {
+        match err {
+            ParseError::Io(err) => LedgerError::Io(err),
+            ParseError::MalformedRow { line, column, kind } => {
+                LedgerError::MalformedRow { line, column, kind }
+            }
+        }
+    }
+}
+
+/// The reason why a single database row couldn't be parsed
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum RowError {
+    #[error("the row has too few items")]
+    TooFewItems,
+
+    #[error("the row has too many items")]
+    TooManyItems,
+
+    #[error("unable to parse the left ID: {0}")]
+    InvalidLeftId(ParseIntError),
+
+    #[error("unable to parse the right ID: {0}")]
+    InvalidRightId(ParseIntError),
+
+    #[error("unable to parse the number of parents: {0}")]
+    InvalidParentCount(ParseIntError),
+
+    #[error("the row has {0} parents, but at most {max} are supported", max = MAX_PARENTS)]
+    TooManyParents(usize),
+
+    #[error("unable to parse the parent ID: {0}")]
+    InvalidParentId(ParseIntError),
+
+    #[error("unable to parse the timestamp: {0}")]
+    InvalidTimestamp(ParseIntError),
+
+    #[error("the row isn't valid UTF-8: {0}")]
+    InvalidUtf8(Utf8Error),
+}
+
+fn invalid_vertex_id_message(id: Id, max_id: Id) -> String {
+    if id == 0 {
+        "the graph cannot have the vertex with ID 0. The minimum is 1".to_string()
+    } else {
+        format!("vertex with ID {id} doesn't exist. Max number is {max_id}")
+    }
+}
@@ -0,0 +1,163 @@
+use std::{collections::HashSet, io::Write};
+
+use crate::{error::LedgerError, graph::Graph};
+
+type Id = usize;
+
+const TIP_COLOR: &str = "lightblue";
+const UNREACHABLE_COLOR: &str = "lightgray";
+const HIGHLIGHT_COLOR: &str = "orange";
+const PAST_CONE_COLOR: &str = "gold";
+
+/// Options of the Graphviz DOT export
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DotOptions {
+    /// Adds the root depth to the labels of the reachable vertices
+    pub label_root_depth: bool,
+    /// Adds the number of inbound references to the labels
+    pub label_inbounds: bool,
+    pub color_tips: bool,
+    pub color_unreachable: bool,
+    /// Highlights the vertex and its past cone
+    pub highlight_past_cone: Option<Id>,
+}
+
+/// Writes the graph in the Graphviz DOT format. Each vertex is labeled with its ID and timestamp
+/// and the edges point from the approving vertex to the approved one. If a vertex belongs to
+/// more than one colored group, the highlighted past cone takes precedence over the tips and
+/// the unreachable vertices.
+pub fn write_dot(
+    graph: &Graph,
+    options: &DotOptions,
+    mut writer: impl Write,
+) -> Result<(), LedgerError> {
+    let past_cone: HashSet<Id> = match options.highlight_past_cone {
+        Some(id) => graph.past_cone(id)?.collect(),
+        None => HashSet::new(),
+    };
+
+    writeln!(writer, "digraph ledger {{")?;
+    writeln!(writer, "    rankdir=RL;")?;
+    writeln!(
+        writer,
+        "    node [shape=box, style=filled, fillcolor=white];"
+    )?;
+
+    for (idx, vertex) in graph.graph.iter().enumerate() {
+        let id = idx + 1;
+        let mut label = format!("{id}\\nt={}", vertex.vertex.timestamp);
+        if options.label_root_depth && vertex.visited {
+            label.push_str(&format!("\\ndepth={}", vertex.root_depth));
+        }
+        if options.label_inbounds {
+            label.push_str(&format!("\\ninbounds={}", graph.approvers(id).len()));
+        }
+
+        let color = if options.highlight_past_cone == Some(id) {
+            Some(HIGHLIGHT_COLOR)
+        } else if past_cone.contains(&id) {
+            Some(PAST_CONE_COLOR)
+        } else if options.color_unreachable && !vertex.visited {
+            Some(UNREACHABLE_COLOR)
+        } else if options.color_tips && graph.approvers(id).len() == 0 {
+            Some(TIP_COLOR)
+        } else {
+            None
+        };
+
+        match color {
+            Some(color) => writeln!(writer, "    {id} [label=\"{label}\", fillcolor={color}];")?,
+            None => writeln!(writer, "    {id} [label=\"{label}\"];")?,
+        }
+    }
+
+    for (idx, vertex) in graph.graph.iter().enumerate() {
+        let id = idx + 1;
+        let parents = &vertex.vertex.parents;
+        for (position, parent) in parents.iter().enumerate() {
+            // the same parent referenced more than once is drawn as one edge
+            if parents[..position].contains(parent) {
+                continue;
+            }
+            writeln!(writer, "    {id} -> {parent};")?;
+        }
+    }
+
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::vertex::{Parents, Vertex};
+
+    use super::*;
+
+    fn graph() -> Graph {
+        let mut graph = Graph::new(vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([1, 1]),
+                timestamp: 1,
+            },
+            Vertex {
+                parents: Parents::from([2]),
+                timestamp: 2,
+            },
+        ]);
+        graph.walk_and_analyze().expect("valid graph");
+        graph
+    }
+
+    fn to_string(graph: &Graph, options: &DotOptions) -> String {
+        let mut output = vec![];
+        write_dot(graph, options, &mut output).expect("writing to vector");
+        String::from_utf8(output).expect("valid UTF-8")
+    }
+
+    #[test]
+    fn test_write_dot() {
+        let dot = to_string(&graph(), &Default::default());
+        assert!(dot.starts_with("digraph ledger {\n"), "{dot}");
+        assert!(dot.contains("    2 [label=\"2\\nt=1\"];\n"), "{dot}");
+        assert!(dot.contains("    2 -> 1;\n"), "{dot}");
+        assert!(dot.contains("    3 -> 2;\n"), "{dot}");
+        assert_eq!(2, dot.matches("->").count(), "{dot}");
+    }
+
+    #[test]
+    fn test_write_dot_labels_and_colors() {
+        let options = DotOptions {
+            label_root_depth: true,
+            label_inbounds: true,
+            color_tips: true,
+            highlight_past_cone: Some(2),
+            ..Default::default()
+        };
+        let dot = to_string(&graph(), &options);
+        assert!(
+            dot.contains("    1 [label=\"1\\nt=0\\ndepth=0\\ninbounds=2\", fillcolor=gold];\n"),
+            "{dot}"
+        );
+        assert!(dot.contains("fillcolor=orange"), "{dot}");
+        assert!(
+            dot.contains(
+                "    3 [label=\"3\\nt=2\\ndepth=2\\ninbounds=0\", fillcolor=lightblue];\n"
+            ),
+            "{dot}"
+        );
+    }
+
+    #[test]
+    fn test_write_dot_invalid_highlight() {
+        let options = DotOptions {
+            highlight_past_cone: Some(9),
+            ..Default::default()
+        };
+        let err = write_dot(&graph(), &options, vec![]).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidVertexId { id: 9, .. }),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use std::io::Write;
+
+use crate::graph::Graph;
+
+mod dot;
+pub use dot::{write_dot, DotOptions};
+
+/// Writes the analyzed vertices as CSV with the header
+/// `id,parents,timestamp,root_depth,inbounds`. The parents are separated by spaces, and the root
+/// depth of the unreachable vertices is left empty.
+pub fn write_csv(graph: &Graph, mut writer: impl Write) -> std::io::Result<()> {
+    writeln!(writer, "id,parents,timestamp,root_depth,inbounds")?;
+    for (idx, vertex) in graph.graph.iter().enumerate() {
+        let parents: Vec<String> = vertex
+            .vertex
+            .parents
+            .iter()
+            .map(|parent| parent.to_string())
+            .collect();
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            idx + 1,
+            parents.join(" "),
+            vertex.vertex.timestamp,
+            optional(Some(vertex.root_depth).filter(|_| vertex.visited)),
+            graph.approvers(idx + 1).len(),
+        )?;
+    }
+    Ok(())
+}
+
+fn optional(value: Option<usize>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        graph::Graph,
+        vertex::{Parents, Vertex},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_write_csv() {
+        let mut graph = Graph::new(vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([1]),
+                timestamp: 3,
+            },
+        ]);
+        graph.walk_and_analyze().expect("valid graph");
+
+        let mut output = vec![];
+        write_csv(&graph, &mut output).expect("writing to vector");
+        assert_eq!(
+            "id,parents,timestamp,root_depth,inbounds\n1,,0,0,1\n2,1,3,1,0\n",
+            String::from_utf8(output).expect("valid UTF-8")
+        );
+    }
+
+    #[test]
+    fn test_write_csv_many_parents() {
+        let mut graph = Graph::new(vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([1]),
+                timestamp: 3,
+            },
+            Vertex {
+                parents: Parents::from([1, 2, 2]),
+                timestamp: 4,
+            },
+        ]);
+        graph.walk_and_analyze().expect("valid graph");
+
+        let mut output = vec![];
+        write_csv(&graph, &mut output).expect("writing to vector");
+        assert_eq!(
+            "id,parents,timestamp,root_depth,inbounds\n1,,0,0,2\n2,1,3,1,2\n3,1 2 2,4,1,0\n",
+            String::from_utf8(output).expect("valid UTF-8")
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Following the database that is still being written, like `tail -f`
+
+use std::io::BufRead;
+
+use log::debug;
+
+use crate::{
+    database::line_as_str,
+    error::LedgerError,
+    vertex::{RowFormat, Vertex},
+};
+
+/// The iterator over the rows appended to the growing database. Only the complete rows are
+/// parsed: the row without the line ending is kept until the rest of it is written.
+///
+/// Unlike most iterators, it returns `None` when no complete row is available yet, and yields
+/// the next vertices once they're appended. The number of vertices declared in the header is
+/// only the lower bound, as the database grows after the header is written.
+pub struct FollowRows<R> {
+    reader: R,
+    line: Vec<u8>,
+    line_number: usize,
+    declared: Option<usize>,
+    format: RowFormat,
+}
+
+impl<R: BufRead> FollowRows<R> {
+    /// The header doesn't have to be written yet
+    pub fn new(reader: R) -> Self {
+        FollowRows {
+            reader,
+            line: vec![],
+            line_number: 0,
+            declared: None,
+            format: RowFormat::default(),
+        }
+    }
+
+    /// Parses the rows in the given format instead of the two-parent one
+    pub fn with_row_format(mut self, format: RowFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The number of vertices declared in the header or `None` if it hasn't been read yet
+    pub fn declared_vertices(&self) -> Option<usize> {
+        self.declared
+    }
+
+    /// Reads the next complete line, or returns `None` if only a part of it is written yet
+    fn read_line(&mut self) -> Result<Option<&str>, LedgerError> {
+        if self.line.last() == Some(&b'\n') {
+            self.line.clear();
+        }
+        self.reader.read_until(b'\n', &mut self.line)?;
+        if self.line.last() != Some(&b'\n') {
+            return Ok(None);
+        }
+        self.line_number += 1;
+        Ok(Some(line_as_str(&self.line)?))
+    }
+}
+
+impl<R: BufRead> Iterator for FollowRows<R> {
+    type Item = Result<Vertex, LedgerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.declared.is_none() {
+            let header = match self.read_line() {
+                Ok(Some(header)) => header,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+            let declared = match header.parse() {
+                Ok(declared) => declared,
+                Err(source) => {
+                    return Some(Err(LedgerError::InvalidHeader {
+                        header: header.to_string(),
+                        source,
+                    }))
+                }
+            };
+            debug!("Extracted lower bound of nodes in graph: {declared}");
+            self.declared = Some(declared);
+        }
+
+        let line_number = self.line_number + 1;
+        let format = self.format;
+        match self.read_line() {
+            Ok(Some(line)) => Some(
+                Vertex::parse_row_with_format(line, line_number, format).map_err(LedgerError::from),
+            ),
+            Ok(None) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::File,
+        io::{BufReader, Write},
+    };
+
+    use super::*;
+    use crate::vertex::Parents;
+
+    #[test]
+    fn test_follow_rows() {
+        let path = std::env::temp_dir().join(format!("ledger-follow-{}.txt", std::process::id()));
+        let mut writer = File::create(&path).expect("creating temporary file");
+        let mut rows = FollowRows::new(BufReader::new(File::open(&path).expect("opening file")));
+        let mut append = |text: &str| {
+            writer
+                .write_all(text.as_bytes())
+                .expect("writing temporary file");
+            writer.flush().expect("writing temporary file");
+        };
+
+        assert!(rows.next().is_none());
+        append("1\n1 1");
+        assert!(rows.next().is_none());
+        assert_eq!(Some(1), rows.declared_vertices());
+
+        append(" 0\n3 1 1\n1 ");
+        let vertices: Vec<Vertex> = rows.by_ref().map(|row| row.expect("valid row")).collect();
+        assert_eq!(2, vertices.len());
+        assert_eq!(Parents::from([1]), vertices[1].parents);
+
+        append("1 x\n");
+        let err = rows
+            .next()
+            .expect("complete row")
+            .expect_err("malformed row");
+        assert!(
+            matches!(err, LedgerError::MalformedRow { line: 4, .. }),
+            "{err}"
+        );
+        assert!(rows.next().is_none());
+
+        std::fs::remove_file(&path).expect("removing temporary file");
+    }
+}
@@ -0,0 +1,105 @@
+use crate::vertex::{Parents, Vertex};
+
+type Id = usize;
+type Timestamp = u32;
+
+/// Parameters of a randomly generated ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneratorConfig {
+    /// The number of vertices in the database (without the root)
+    pub vertices: usize,
+    /// The parents of a new vertex are chosen from that many most recent vertices
+    pub window: usize,
+    /// The maximal difference between the timestamps of consecutive vertices. The timestamps
+    /// stop growing at `Timestamp::MAX`.
+    pub max_time_step: Timestamp,
+    pub seed: u64,
+}
+
+/// Generates a tangle-like ledger in which every vertex approves two random recent vertices.
+/// Like [`crate::database::load_vertices_from_database`], the result starts with the root.
+pub fn generate_vertices(config: &GeneratorConfig) -> Vec<Vertex> {
+    let mut rng = SplitMix64(config.seed);
+    let mut vertices = Vec::with_capacity(config.vertices + 1);
+    vertices.push(Vertex::default());
+
+    let mut timestamp: Timestamp = 0;
+    for idx in 1..=config.vertices {
+        // the IDs of the existing vertices are 1..=idx
+        let oldest: Id = idx.saturating_sub(config.window.max(1)) + 1;
+        let mut pick_parent = || oldest + rng.next_below((idx - oldest + 1) as u64) as Id;
+        let left = pick_parent();
+        let right = pick_parent();
+        let step = rng.next_below(config.max_time_step as u64 + 1) as Timestamp;
+        timestamp = timestamp.saturating_add(step);
+
+        vertices.push(Vertex {
+            parents: Parents::from([left, right]),
+            timestamp,
+        });
+    }
+    vertices
+}
+
+/// Small, deterministic pseudo-random number generator, good enough for test data
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_generate_vertices() {
+        let config = GeneratorConfig {
+            vertices: 100,
+            window: 5,
+            max_time_step: 3,
+            seed: 7,
+        };
+        let vertices = generate_vertices(&config);
+
+        assert_eq!(101, vertices.len());
+        assert_eq!(Vertex::default(), vertices[0]);
+        for (idx, vertex) in vertices.iter().enumerate().skip(1) {
+            assert_eq!(
+                2,
+                vertex.parents.len(),
+                "generated vertices have both parents"
+            );
+            for &parent in vertex.parents.iter() {
+                assert!(parent <= idx && parent + 5 > idx, "{idx}: {parent}");
+            }
+            assert!(vertex.timestamp >= vertices[idx - 1].timestamp);
+            assert!(vertex.timestamp <= vertices[idx - 1].timestamp + 3);
+        }
+        assert_eq!(vertices, generate_vertices(&config));
+    }
+
+    #[test]
+    fn test_generate_vertices_timestamp_saturates() {
+        let vertices = generate_vertices(&GeneratorConfig {
+            vertices: 100,
+            window: 5,
+            max_time_step: Timestamp::MAX,
+            seed: 7,
+        });
+        assert_eq!(Timestamp::MAX, vertices[100].timestamp);
+        for (previous, vertex) in vertices.iter().zip(&vertices[1..]) {
+            assert!(vertex.timestamp >= previous.timestamp);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::{collections::HashMap, iter::Copied, mem::size_of, slice};
+
+use super::{check_valid_id, vertex_with_stats::VertexWithStats};
+use crate::error::LedgerError;
+
+type Id = usize;
+
+/// The inward references (the approvers) of all the vertices in the compressed sparse row
+/// layout: one flat array of the approvers grouped by the approved vertex and ordered by ID,
+/// and the offset of each group. Compared to a `Vec` per vertex, there are only two heap
+/// allocations and the BFS reads the approvers sequentially.
+///
+/// The vertex approved by the vertex added with [`super::Graph::push_vertex`] has its approvers
+/// moved out of the flat array until there are more of the added approvers than in the array,
+/// and then the arrays are rebuilt, so appending is amortized O(1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Adjacency {
+    /// `offsets[idx]..offsets[idx + 1]` is the range of the approvers of the vertex in
+    /// `approvers`
+    offsets: Vec<usize>,
+    approvers: Vec<Id>,
+    /// All the approvers of the vertices that gained any after the arrays were built
+    appended: HashMap<Id, Vec<Id>>,
+    /// The number of approvers gained after the arrays were built
+    appended_count: usize,
+}
+
+impl Default for Adjacency {
+    fn default() -> Self {
+        Adjacency {
+            offsets: vec![0],
+            approvers: vec![],
+            appended: HashMap::new(),
+            appended_count: 0,
+        }
+    }
+}
+
+impl Adjacency {
+    /// Finds the approvers of all the vertices in two passes over the parents: the first one
+    /// counts the approvers of each vertex, the second one places them.
+    pub fn build(graph: &[VertexWithStats]) -> Result<Adjacency, LedgerError> {
+        let max_id = graph.len();
+        let mut offsets = vec![0; max_id + 1];
+        for vertex in graph {
+            for parent in vertex.parents() {
+                check_valid_id(parent, max_id)?;
+                offsets[parent] += 1;
+            }
+        }
+        for idx in 1..offsets.len() {
+            offsets[idx] += offsets[idx - 1];
+        }
+
+        // the approvers are visited in the order of IDs, so every group ends up sorted
+        let mut cursors = offsets[..max_id].to_vec();
+        let mut approvers = vec![0; offsets[max_id]];
+        for (idx, vertex) in graph.iter().enumerate() {
+            for parent in vertex.parents() {
+                approvers[cursors[parent - 1]] = idx + 1;
+                cursors[parent - 1] += 1;
+            }
+        }
+
+        Ok(Adjacency::from_csr(offsets, approvers))
+    }
+
+    /// Creates the adjacency from the approvers of each vertex
+    pub fn from_approvers<I>(approvers: impl IntoIterator<Item = I>) -> Adjacency
+    where
+        I: IntoIterator<Item = Id>,
+    {
+        let mut adjacency = Adjacency::default();
+        for vertex_approvers in approvers {
+            adjacency.approvers.extend(vertex_approvers);
+            adjacency.offsets.push(adjacency.approvers.len());
+        }
+        adjacency
+    }
+
+    pub(super) fn from_csr(offsets: Vec<usize>, approvers: Vec<Id>) -> Adjacency {
+        Adjacency {
+            offsets,
+            approvers,
+            appended: HashMap::new(),
+            appended_count: 0,
+        }
+    }
+
+    /// The number of vertices
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of the inward references of all the vertices
+    pub fn edges(&self) -> usize {
+        self.approvers.len() + self.appended_count
+    }
+
+    /// Returns the iterator over the approvers of the vertex, ordered by ID. The vertex that
+    /// isn't in the adjacency has no approvers.
+    pub fn approvers(&self, id: Id) -> Approvers<'_> {
+        self.slice(id).iter().copied()
+    }
+
+    /// Returns the number of approvers of the vertex
+    pub fn count(&self, id: Id) -> usize {
+        self.slice(id).len()
+    }
+
+    fn slice(&self, id: Id) -> &[Id] {
+        // hashing the ID would cost more than reading the approvers
+        if self.appended_count > 0 {
+            if let Some(approvers) = self.appended.get(&id) {
+                return approvers;
+            }
+        }
+        match id.checked_sub(1).filter(|idx| *idx < self.len()) {
+            Some(idx) => &self.approvers[self.offsets[idx]..self.offsets[idx + 1]],
+            None => &[],
+        }
+    }
+
+    /// The memory used by the adjacency on the heap, in bytes
+    pub fn heap_size(&self) -> usize {
+        self.offsets.capacity() * size_of::<usize>()
+            + self.approvers.capacity() * size_of::<Id>()
+            + self.appended.capacity() * size_of::<(Id, Vec<Id>)>()
+            + self
+                .appended
+                .values()
+                .map(|approvers| approvers.capacity() * size_of::<Id>())
+                .sum::<usize>()
+    }
+
+    /// Adds the vertex without approvers
+    pub(super) fn push_vertex(&mut self) {
+        self.offsets.push(self.approvers.len());
+    }
+
+    /// Adds the approver to the vertex. Both of them must exist.
+    pub(super) fn push_approver(&mut self, id: Id, approver: Id) {
+        // the approvers are moved out of the flat array, so they can still be read as one slice
+        if !self.appended.contains_key(&id) {
+            let built = self.slice(id).to_vec();
+            self.appended.insert(id, built);
+        }
+        self.appended
+            .get_mut(&id)
+            .expect("the approvers were moved")
+            .push(approver);
+        self.appended_count += 1;
+        if self.appended_count > self.approvers.len() {
+            self.rebuild();
+        }
+    }
+
+    /// Moves the appended approvers into the flat array
+    fn rebuild(&mut self) {
+        let mut appended = std::mem::take(&mut self.appended);
+        let vertices = self.len();
+        let mut offsets = Vec::with_capacity(vertices + 1);
+        let mut approvers = Vec::with_capacity(self.edges());
+        offsets.push(0);
+        for id in 1..=vertices {
+            match appended.remove(&id) {
+                Some(appended) => approvers.extend(appended),
+                None => approvers
+                    .extend_from_slice(&self.approvers[self.offsets[id - 1]..self.offsets[id]]),
+            }
+            offsets.push(approvers.len());
+        }
+        *self = Adjacency::from_csr(offsets, approvers);
+    }
+}
+
+/// Iterator over the approvers of a vertex
+pub type Approvers<'a> = Copied<slice::Iter<'a, Id>>;
+
+#[cfg(test)]
+mod test {
+    use crate::vertex::{Parents, Vertex};
+
+    use super::*;
+
+    fn graph() -> Vec<VertexWithStats> {
+        [
+            Parents::default(),
+            Parents::from([1, 1]),
+            Parents::from([1, 2]),
+            Parents::from([3]),
+        ]
+        .into_iter()
+        .map(|parents| {
+            VertexWithStats::from(Vertex {
+                parents,
+                ..Default::default()
+            })
+        })
+        .collect()
+    }
+
+    fn approvers(adjacency: &Adjacency) -> Vec<Vec<Id>> {
+        (1..=adjacency.len())
+            .map(|id| adjacency.approvers(id).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_build_adjacency() {
+        let adjacency = Adjacency::build(&graph()).expect("valid graph");
+        assert_eq!(
+            vec![vec![2, 2, 3], vec![3], vec![4], vec![]],
+            approvers(&adjacency)
+        );
+        assert_eq!(5, adjacency.edges());
+        assert_eq!(3, adjacency.count(1));
+        assert_eq!(
+            Adjacency::from_approvers([vec![2, 2, 3], vec![3], vec![4], vec![]]),
+            adjacency
+        );
+    }
+
+    #[test]
+    fn test_build_adjacency_invalid_id() {
+        let mut graph = graph();
+        graph[3].vertex.parents.push(5);
+        let err = Adjacency::build(&graph).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidVertexId { id: 5, max_id: 4 }),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_push_vertex_to_adjacency() {
+        let mut adjacency = Adjacency::build(&graph()).expect("valid graph");
+        adjacency.push_vertex();
+        adjacency.push_approver(4, 5);
+        adjacency.push_approver(1, 5);
+        assert_eq!(
+            vec![vec![2, 2, 3, 5], vec![3], vec![4], vec![5], vec![]],
+            approvers(&adjacency)
+        );
+        assert_eq!(2, adjacency.appended.len());
+
+        // the appended approvers outnumber the built ones, so the arrays are rebuilt
+        for id in 6..10 {
+            adjacency.push_vertex();
+            adjacency.push_approver(2, id);
+        }
+        assert!(adjacency.appended.is_empty());
+        assert_eq!(vec![3, 6, 7, 8, 9], approvers(&adjacency)[1]);
+        assert_eq!(11, adjacency.edges());
+    }
+}
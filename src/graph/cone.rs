@@ -0,0 +1,156 @@
+use super::{adjacency::Adjacency, check_valid_id, vertex_with_stats::VertexWithStats};
+use crate::error::LedgerError;
+
+type Id = usize;
+
+#[derive(Debug, Clone, Copy)]
+enum Direction<'a> {
+    /// Along the edges to the parents
+    Past,
+    /// Along the inward references to the approving vertices
+    Future(&'a Adjacency),
+}
+
+/// Iterator over the IDs of the vertices that the vertex directly or indirectly approves (the
+/// past cone) or that directly or indirectly approve it (the future cone). The vertex itself
+/// isn't part of its cone. The order of the IDs is unspecified.
+pub struct Cone<'a> {
+    graph: &'a [VertexWithStats],
+    direction: Direction<'a>,
+    visited: Vec<bool>,
+    to_visit: Vec<Id>,
+}
+
+impl<'a> Cone<'a> {
+    fn new(
+        graph: &'a [VertexWithStats],
+        id: Id,
+        direction: Direction<'a>,
+    ) -> Result<Self, LedgerError> {
+        check_valid_id(id, graph.len())?;
+
+        let mut cone = Cone {
+            graph,
+            direction,
+            visited: vec![false; graph.len()],
+            to_visit: vec![],
+        };
+        cone.visited[id - 1] = true;
+        cone.push_neighbours(id);
+        Ok(cone)
+    }
+
+    fn push_neighbours(&mut self, id: Id) {
+        let vertex = &self.graph[id - 1];
+        match self.direction {
+            Direction::Past => {
+                for parent in vertex.vertex.parents.iter() {
+                    self.push(*parent)
+                }
+            }
+            Direction::Future(inbounds) => {
+                for approver in inbounds.approvers(id) {
+                    self.push(approver)
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, id: Id) {
+        // the references are validated while finding the inward references
+        if let Some(visited) = id.checked_sub(1).and_then(|idx| self.visited.get_mut(idx)) {
+            if !*visited {
+                *visited = true;
+                self.to_visit.push(id);
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Cone<'a> {
+    type Item = Id;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.to_visit.pop()?;
+        self.push_neighbours(id);
+        Some(id)
+    }
+}
+
+/// returns the iterator over the past cone of the vertex
+pub fn past_cone(graph: &[VertexWithStats], id: Id) -> Result<Cone<'_>, LedgerError> {
+    Cone::new(graph, id, Direction::Past)
+}
+
+/// returns the iterator over the future cone of the vertex
+pub fn future_cone<'a>(
+    graph: &'a [VertexWithStats],
+    inbounds: &'a Adjacency,
+    id: Id,
+) -> Result<Cone<'a>, LedgerError> {
+    Cone::new(graph, id, Direction::Future(inbounds))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        graph::find_inward_references,
+        vertex::{Parents, Vertex},
+    };
+
+    use super::*;
+
+    fn graph() -> (Vec<VertexWithStats>, Adjacency) {
+        let graph: Vec<VertexWithStats> = [
+            Parents::default(),
+            Parents::from([1, 1]),
+            Parents::from([1, 2]),
+            Parents::from([2, 2]),
+            Parents::from([3, 3]),
+            Parents::from([3, 4]),
+        ]
+        .into_iter()
+        .map(|parents| {
+            VertexWithStats::from(Vertex {
+                parents,
+                ..Default::default()
+            })
+        })
+        .collect();
+        let inbounds = find_inward_references(&graph).expect("valid graph");
+        (graph, inbounds)
+    }
+
+    #[test]
+    fn test_past_cone() {
+        let (graph, _) = graph();
+        let mut cone: Vec<Id> = past_cone(&graph, 6).expect("valid ID").collect();
+        cone.sort();
+        assert_eq!(vec![1, 2, 3, 4], cone);
+        assert_eq!(0, past_cone(&graph, 1).expect("valid ID").count());
+    }
+
+    #[test]
+    fn test_future_cone() {
+        let (graph, inbounds) = graph();
+        let mut cone: Vec<Id> = future_cone(&graph, &inbounds, 2)
+            .expect("valid ID")
+            .collect();
+        cone.sort();
+        assert_eq!(vec![3, 4, 5, 6], cone);
+        assert_eq!(
+            0,
+            future_cone(&graph, &inbounds, 6).expect("valid ID").count()
+        );
+    }
+
+    #[test]
+    fn test_cone_invalid_id() {
+        let (graph, _) = graph();
+        let err = past_cone(&graph, 7).err().expect("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidVertexId { id: 7, max_id: 6 }),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::Display;
+
+use super::{
+    adjacency::Adjacency, cumulative_weight::count_approvers, reached_with_ids, tips::find_tips,
+    vertex_with_stats::VertexWithStats,
+};
+use crate::error::LedgerError;
+
+type Id = usize;
+type Timestamp = u32;
+
+/// The condition a vertex must meet to be confirmed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfirmationThreshold {
+    /// The minimal cumulative weight of the vertex
+    Weight(usize),
+    /// The minimal fraction of tips that directly or indirectly approve the vertex
+    TipFraction(f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfirmationConfig {
+    pub threshold: ConfirmationThreshold,
+    /// An unconfirmed vertex older than that (relative to the latest timestamp) is unlikely to
+    /// be approved anymore, so it's at risk of being orphaned
+    pub max_pending_age: Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmationStatus {
+    Confirmed,
+    Pending,
+    OrphanRisk,
+}
+
+impl Display for ConfirmationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfirmationStatus::Confirmed => write!(f, "confirmed"),
+            ConfirmationStatus::Pending => write!(f, "pending"),
+            ConfirmationStatus::OrphanRisk => write!(f, "orphan-risk"),
+        }
+    }
+}
+
+/// classifies the vertices reached during the analysis. The result contains the ID and the
+/// status of each vertex. **Before classifying with [`ConfirmationThreshold::Weight`] you must
+/// find the cumulative weight**.
+pub fn classify_confirmations(
+    graph: &[VertexWithStats],
+    inbounds: &Adjacency,
+    config: &ConfirmationConfig,
+) -> Result<Vec<(Id, ConfirmationStatus)>, LedgerError> {
+    let confirmed: Vec<bool> = match config.threshold {
+        ConfirmationThreshold::Weight(weight) => graph
+            .iter()
+            .map(|vertex| vertex.cumulative_weight >= weight)
+            .collect(),
+        ConfirmationThreshold::TipFraction(fraction) => {
+            let tips = find_tips(graph, inbounds);
+            let mut is_tip = vec![false; graph.len()];
+            for id in &tips {
+                is_tip[id - 1] = true;
+            }
+            // the tip doesn't approve itself
+            count_approvers(graph, inbounds, &is_tip)?
+                .into_iter()
+                .zip(&is_tip)
+                .map(|(approving_tips, is_tip)| {
+                    (approving_tips - *is_tip as usize) as f64 >= fraction * tips.len() as f64
+                })
+                .collect()
+        }
+    };
+
+    let latest = reached_with_ids(graph)
+        .map(|(_, vertex)| vertex.vertex.timestamp)
+        .max()
+        .unwrap_or_default();
+
+    Ok(reached_with_ids(graph)
+        .map(|(id, vertex)| {
+            let status = if confirmed[id - 1] {
+                ConfirmationStatus::Confirmed
+            } else if latest - vertex.vertex.timestamp > config.max_pending_age {
+                ConfirmationStatus::OrphanRisk
+            } else {
+                ConfirmationStatus::Pending
+            };
+            (id, status)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        graph::{cumulative_weight::find_cumulative_weight, find_inward_references},
+        vertex::{Parents, Vertex},
+    };
+
+    use super::*;
+
+    fn graph() -> (Vec<VertexWithStats>, Adjacency) {
+        // 4 and 5 are tips, 3 is approved only by 5
+        let mut graph: Vec<VertexWithStats> = [
+            (Parents::default(), 0),
+            (Parents::from([1, 1]), 1),
+            (Parents::from([1, 2]), 2),
+            (Parents::from([2, 2]), 3),
+            (Parents::from([3, 3]), 9),
+        ]
+        .into_iter()
+        .map(|(parents, timestamp)| VertexWithStats::from(Vertex { parents, timestamp }))
+        .collect();
+        let inbounds = find_inward_references(&graph).expect("valid graph");
+        for vertex in graph.iter_mut() {
+            vertex.visited = true;
+        }
+        (graph, inbounds)
+    }
+
+    fn statuses(
+        graph: &[VertexWithStats],
+        inbounds: &Adjacency,
+        threshold: ConfirmationThreshold,
+    ) -> Vec<ConfirmationStatus> {
+        let config = ConfirmationConfig {
+            threshold,
+            max_pending_age: 6,
+        };
+        classify_confirmations(graph, inbounds, &config)
+            .expect("shouldn't return error")
+            .into_iter()
+            .map(|(_, status)| status)
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_confirmations_weight() {
+        let (mut graph, inbounds) = graph();
+        find_cumulative_weight(&mut graph, &inbounds).expect("shouldn't return error");
+        use ConfirmationStatus::*;
+        assert_eq!(
+            vec![Confirmed, Confirmed, OrphanRisk, Pending, Pending],
+            statuses(&graph, &inbounds, ConfirmationThreshold::Weight(3))
+        );
+    }
+
+    #[test]
+    fn test_classify_confirmations_tip_fraction() {
+        let (graph, inbounds) = graph();
+        use ConfirmationStatus::*;
+        assert_eq!(
+            vec![Confirmed, Confirmed, OrphanRisk, Pending, Pending],
+            statuses(&graph, &inbounds, ConfirmationThreshold::TipFraction(1.0))
+        );
+        assert_eq!(
+            vec![Confirmed, Confirmed, Confirmed, Pending, Pending],
+            statuses(&graph, &inbounds, ConfirmationThreshold::TipFraction(0.5))
+        );
+    }
+}
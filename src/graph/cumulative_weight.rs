@@ -0,0 +1,380 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use log::debug;
+
+use super::{adjacency::Adjacency, check_valid_id, vertex_with_stats::VertexWithStats};
+use crate::error::LedgerError;
+
+/// The number of 64-bit words of the approval mask. Every batch computes the weights of
+/// `LANES * 64` vertices at once.
+const LANES: usize = 4;
+const BATCH_SIZE: usize = LANES * u64::BITS as usize;
+
+type Mask = [u64; LANES];
+
+/// finds the cumulative weight (1 + the number of distinct vertices that directly or
+/// indirectly approve the vertex) for each vertex.
+///
+/// Counting the distinct approvers requires the set union, so the vertices are processed in
+/// topological order in batches of 256 vertices. For every batch a bit mask of the batch
+/// members approved by the vertex is propagated from the parents to the approvers and the
+/// number of set bits is summed with bit-sliced counters. The approvers of a batch can appear
+/// only after the batch in the topological order, so each batch visits only the remaining part
+/// of the graph and the memory usage stays linear.
+///
+/// In a ledger the new vertices approve the recent ones, so soon after the batch every new
+/// vertex approves the same members. The batch ends as soon as all the vertices that are still
+/// going to be referenced approve the same members and every remaining vertex references a
+/// vertex from or after the batch, because then all the remaining vertices approve exactly
+/// these members. It also ends as soon as none of them approves a member, e.g. after the
+/// component of the batch in a graph of many components, because then no remaining vertex does.
+pub fn find_cumulative_weight(
+    graph: &mut [VertexWithStats],
+    inbounds: &Adjacency,
+) -> Result<(), LedgerError> {
+    let weights = count_approvers(graph, inbounds, &vec![true; graph.len()])?;
+    for (vertex, weight) in graph.iter_mut().zip(weights) {
+        vertex.cumulative_weight = weight;
+    }
+    Ok(())
+}
+
+/// Counts for each vertex the distinct vertices that approve it, including the vertex itself,
+/// but only the vertices whose index is marked in `counted`. See [`find_cumulative_weight`]
+/// for the algorithm.
+pub(super) fn count_approvers(
+    graph: &[VertexWithStats],
+    inbounds: &Adjacency,
+    counted: &[bool],
+) -> Result<Vec<usize>, LedgerError> {
+    if graph.is_empty() {
+        return Err(LedgerError::EmptyGraph);
+    }
+    let order = topological_order(graph, inbounds)?;
+    let mut position = vec![0; graph.len()];
+    for (pos, idx) in order.iter().enumerate() {
+        position[*idx] = pos;
+    }
+    let last_use = find_last_use(inbounds, &position);
+    let latest_parent = find_min_latest_parent(graph, &order, &position);
+    // the number of counted vertices from the position onwards
+    let mut counted_from = vec![0; order.len() + 1];
+    for (pos, idx) in order.iter().enumerate().rev() {
+        counted_from[pos] = counted_from[pos + 1] + counted[*idx] as usize;
+    }
+    let mut approvers = vec![0; graph.len()];
+
+    let mut masks: Vec<Mask> = vec![[0; LANES]; graph.len()];
+    // the number of bits needed to count all the vertices
+    let planes = (usize::BITS - graph.len().leading_zeros()) as usize;
+
+    for batch_start in (0..order.len()).step_by(BATCH_SIZE) {
+        debug!("counting approvers of batch starting at {batch_start}");
+        let batch_end = (batch_start + BATCH_SIZE).min(order.len());
+        let mut counters: Vec<Mask> = vec![[0; LANES]; planes];
+        // (the position of the last approver, index) of the vertices that will be referenced
+        let mut alive: BinaryHeap<Reverse<(usize, usize)>> = BinaryHeap::new();
+        let mut alive_masks: HashMap<Mask, usize> = HashMap::new();
+        let mut saturated_mask: Mask = [0; LANES];
+        let mut saturated = 0;
+
+        for (pos, idx) in order.iter().enumerate().skip(batch_start) {
+            while alive.peek().is_some_and(|Reverse((last, _))| *last < pos) {
+                let Reverse((_, dead_idx)) = alive.pop().expect("alive vertex");
+                let mask = &masks[dead_idx];
+                let count = alive_masks.get_mut(mask).expect("alive mask");
+                *count -= 1;
+                if *count == 0 {
+                    alive_masks.remove(mask);
+                }
+            }
+            if pos >= batch_end
+                && alive_masks.len() == 1
+                && latest_parent[pos].is_some_and(|parent_pos| parent_pos >= batch_start)
+            {
+                saturated_mask = *alive_masks.keys().next().expect("single mask");
+                saturated = counted_from[pos];
+                break;
+            }
+            if pos >= batch_end
+                && alive_masks.len() <= 1
+                && alive_masks.keys().all(|mask| *mask == [0; LANES])
+            {
+                break;
+            }
+
+            let mut mask: Mask = [0; LANES];
+            for parent in graph[*idx].parents() {
+                let parent_idx = parent - 1;
+                if parent_idx != *idx && position[parent_idx] >= batch_start {
+                    for (word, parent_word) in mask.iter_mut().zip(masks[parent_idx]) {
+                        *word |= parent_word;
+                    }
+                }
+            }
+            if pos < batch_end {
+                let offset = pos - batch_start;
+                mask[offset / 64] |= 1 << (offset % 64);
+            }
+            if let Some(last) = last_use[*idx] {
+                alive.push(Reverse((last, *idx)));
+                *alive_masks.entry(mask).or_default() += 1;
+            }
+            masks[*idx] = mask;
+            if counted[*idx] {
+                add_to_counters(&mut counters, &mask);
+            }
+        }
+
+        for (offset, idx) in order[batch_start..batch_end].iter().enumerate() {
+            let approved_by_rest = (saturated_mask[offset / 64] >> (offset % 64)) & 1 == 1;
+            approvers[*idx] =
+                read_counter(&counters, offset) + if approved_by_rest { saturated } else { 0 };
+        }
+    }
+
+    Ok(approvers)
+}
+
+/// Returns the position of the last approver of each vertex in the topological order
+fn find_last_use(inbounds: &Adjacency, position: &[usize]) -> Vec<Option<usize>> {
+    (1..=position.len())
+        .map(|id| {
+            inbounds
+                .approvers(id)
+                .filter(|approver| *approver != id)
+                .map(|approver| position[approver - 1])
+                .max()
+        })
+        .collect()
+}
+
+/// For every position in the topological order returns the minimum, over the vertices from
+/// that position onwards, of the position of their latest parent. `None` means that there is
+/// a vertex without parents.
+fn find_min_latest_parent(
+    graph: &[VertexWithStats],
+    order: &[usize],
+    position: &[usize],
+) -> Vec<Option<usize>> {
+    let mut min_latest_parent = vec![None; order.len()];
+    let mut min_so_far = Some(usize::MAX);
+    for (pos, idx) in order.iter().enumerate().rev() {
+        let latest_parent = graph[*idx]
+            .parents()
+            .filter(|parent| *parent != idx + 1)
+            .map(|parent| position[parent - 1])
+            .max();
+        min_so_far = min_so_far.zip(latest_parent).map(|(a, b)| a.min(b));
+        min_latest_parent[pos] = min_so_far;
+    }
+    min_latest_parent
+}
+
+/// Returns the indexes of the vertices ordered so that every parent precedes its approvers.
+/// The order of the database is kept whenever possible.
+pub(super) fn topological_order(
+    graph: &[VertexWithStats],
+    inbounds: &Adjacency,
+) -> Result<Vec<usize>, LedgerError> {
+    let max_id = graph.len();
+    let mut parents_left = vec![0usize; graph.len()];
+    for (idx, vertex) in graph.iter().enumerate() {
+        for parent in vertex.parents() {
+            check_valid_id(parent, max_id)?;
+            if parent != idx + 1 {
+                parents_left[idx] += 1;
+            }
+        }
+    }
+
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..graph.len())
+        .filter(|idx| parents_left[*idx] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(graph.len());
+    while let Some(Reverse(idx)) = ready.pop() {
+        order.push(idx);
+        for approver in inbounds.approvers(idx + 1) {
+            let approver_idx = approver - 1;
+            if approver_idx == idx {
+                continue;
+            }
+            parents_left[approver_idx] -= 1;
+            if parents_left[approver_idx] == 0 {
+                ready.push(Reverse(approver_idx));
+            }
+        }
+    }
+
+    if order.len() != graph.len() {
+        let cyclic = (0..graph.len())
+            .filter(|idx| parents_left[*idx] > 0)
+            .map(|idx| idx + 1)
+            .collect();
+        return Err(LedgerError::CyclicGraph(cyclic));
+    }
+    Ok(order)
+}
+
+/// Adds every bit of `mask` to the corresponding bit-sliced counter
+#[inline]
+fn add_to_counters(counters: &mut [Mask], mask: &Mask) {
+    for lane in 0..LANES {
+        let mut carry = mask[lane];
+        for plane in counters.iter_mut() {
+            if carry == 0 {
+                break;
+            }
+            let sum = plane[lane] ^ carry;
+            carry &= plane[lane];
+            plane[lane] = sum;
+        }
+    }
+}
+
+fn read_counter(counters: &[Mask], bit: usize) -> usize {
+    counters
+        .iter()
+        .enumerate()
+        .map(|(plane, mask)| (((mask[bit / 64] >> (bit % 64)) & 1) as usize) << plane)
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::LedgerError,
+        graph::find_inward_references,
+        vertex::{Parents, Vertex},
+    };
+
+    use super::*;
+
+    fn graph(parents: &[Parents]) -> (Vec<VertexWithStats>, Adjacency) {
+        let graph: Vec<VertexWithStats> = parents
+            .iter()
+            .map(|parents| {
+                VertexWithStats::from(Vertex {
+                    parents: *parents,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let inbounds = find_inward_references(&graph).expect("valid graph");
+        (graph, inbounds)
+    }
+
+    #[test]
+    fn test_find_cumulative_weight() {
+        let (mut graph, inbounds) = graph(&[
+            Parents::default(),
+            Parents::from([1, 1]),
+            Parents::from([1, 2]),
+            Parents::from([2, 2]),
+            Parents::from([3, 3]),
+            Parents::from([3, 4]),
+        ]);
+        find_cumulative_weight(&mut graph, &inbounds).expect("shouldn't return error");
+        let weights: Vec<usize> = graph.iter().map(|v| v.cumulative_weight).collect();
+        assert_eq!(vec![6, 5, 3, 2, 1, 1], weights);
+    }
+
+    #[test]
+    fn test_find_cumulative_weight_forward_reference() {
+        let (mut graph, inbounds) = graph(&[
+            Parents::default(),
+            Parents::from([1, 3]),
+            Parents::from([1]),
+        ]);
+        find_cumulative_weight(&mut graph, &inbounds).expect("shouldn't return error");
+        let weights: Vec<usize> = graph.iter().map(|v| v.cumulative_weight).collect();
+        assert_eq!(vec![3, 1, 2], weights);
+    }
+
+    #[test]
+    fn test_find_cumulative_weight_multiple_batches() {
+        // a chain: every vertex is approved by all the following ones
+        let size = 3 * BATCH_SIZE + 7;
+        let parents: Vec<_> = (0..size)
+            .map(|idx| Parents::from_slice(&[idx][..idx.min(1)]).expect("one parent"))
+            .collect();
+        let (mut graph, inbounds) = graph(&parents);
+        find_cumulative_weight(&mut graph, &inbounds).expect("shouldn't return error");
+        for (idx, vertex) in graph.iter().enumerate() {
+            assert_eq!(size - idx, vertex.cumulative_weight);
+        }
+    }
+
+    #[test]
+    fn test_find_cumulative_weight_saturated_batches() {
+        // every vertex approves the two previous ones, so the batches end early
+        let size = 3 * BATCH_SIZE + 7;
+        let parents: Vec<_> = (0..size)
+            .map(|idx| {
+                Parents::from_slice(&[idx, idx.saturating_sub(1)][..idx.min(2)])
+                    .expect("two parents")
+            })
+            .collect();
+        let (mut graph, inbounds) = graph(&parents);
+        find_cumulative_weight(&mut graph, &inbounds).expect("shouldn't return error");
+        for (idx, vertex) in graph.iter().enumerate() {
+            assert_eq!(size - idx, vertex.cumulative_weight);
+        }
+    }
+
+    #[test]
+    fn test_find_cumulative_weight_parallel_branches() {
+        // two chains starting at the root never approve each other
+        let size = 2 * BATCH_SIZE + 11;
+        let parents: Vec<_> = (0..size)
+            .map(|idx| match idx {
+                0 => Parents::default(),
+                1 | 2 => Parents::from([1]),
+                _ => Parents::from([idx - 1]),
+            })
+            .collect();
+        let (mut graph, inbounds) = graph(&parents);
+        find_cumulative_weight(&mut graph, &inbounds).expect("shouldn't return error");
+        assert_eq!(size, graph[0].cumulative_weight);
+        for (idx, vertex) in graph.iter().enumerate().skip(1) {
+            assert_eq!((size - idx).div_ceil(2), vertex.cumulative_weight, "{idx}");
+        }
+    }
+
+    #[test]
+    fn test_find_cumulative_weight_resolved_batches() {
+        // short chains without a common root, so the batches never saturate
+        let chain = 5;
+        let size = 2 * BATCH_SIZE + 3;
+        let parents: Vec<_> = (0..size)
+            .map(|idx| match idx % chain {
+                0 => Parents::default(),
+                _ => Parents::from([idx]),
+            })
+            .collect();
+        let (mut graph, inbounds) = graph(&parents);
+        find_cumulative_weight(&mut graph, &inbounds).expect("shouldn't return error");
+        for (idx, vertex) in graph.iter().enumerate() {
+            let chain_end = (idx / chain * chain + chain).min(size);
+            assert_eq!(chain_end - idx, vertex.cumulative_weight, "{idx}");
+        }
+    }
+
+    #[test]
+    fn test_find_cumulative_weight_cycle() {
+        let (mut graph, inbounds) = graph(&[
+            Parents::default(),
+            Parents::from([1, 3]),
+            Parents::from([2]),
+        ]);
+        let err = find_cumulative_weight(&mut graph, &inbounds).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::CyclicGraph(ref ids) if ids == &[2, 3]),
+            "{err}"
+        );
+    }
+}
@@ -1,17 +1,86 @@
-use anyhow::bail;
 use log::trace;
 use std::collections::VecDeque;
 
-use crate::vertex::Vertex;
+use crate::{error::LedgerError, vertex::Vertex};
+mod adjacency;
+mod cone;
+mod confirmation;
+mod cumulative_weight;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+mod roots;
+mod running_stats;
+mod stats;
+mod tips;
+mod validation;
 mod vertex_with_stats;
-use vertex_with_stats::VertexWithStats;
+pub use adjacency::{Adjacency, Approvers};
+pub use cone::Cone;
+pub use confirmation::{ConfirmationConfig, ConfirmationStatus, ConfirmationThreshold};
+pub use roots::RootStats;
+pub use stats::GraphStats;
+pub use validation::{Reference, TimestampViolation, TimestampViolationKind, ValidationReport};
+pub use vertex_with_stats::VertexWithStats;
+
+use running_stats::RunningStats;
 
 type Id = usize;
 type PathLength = usize;
 
+/// The default root: the first vertex, which isn't stored in the database
+const ROOT_ID: Id = 1;
+
+type FindDepthFrom = fn(&mut [VertexWithStats], &Adjacency, &[Id]) -> Result<(), LedgerError>;
+
+/// The implementations of the analysis steps, so the same analysis can be run sequentially or
+/// in parallel
+struct AnalysisSteps {
+    find_inward_references: fn(&[VertexWithStats]) -> Result<Adjacency, LedgerError>,
+    find_depth_from: FindDepthFrom,
+    find_unreachable_vertices: fn(&[VertexWithStats]) -> Vec<Id>,
+    running_stats: fn(&[VertexWithStats], &Adjacency) -> RunningStats,
+}
+
+const SEQUENTIAL: AnalysisSteps = AnalysisSteps {
+    find_inward_references,
+    find_depth_from,
+    find_unreachable_vertices,
+    running_stats: RunningStats::new,
+};
+
+#[cfg(feature = "parallel")]
+const PARALLEL: AnalysisSteps = AnalysisSteps {
+    find_inward_references: parallel::find_inward_references,
+    find_depth_from: parallel::find_depth_from,
+    find_unreachable_vertices: parallel::find_unreachable_vertices,
+    running_stats: parallel::running_stats,
+};
+
+/// Defines how the analysis treats the vertices that cannot be reached from the root
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnreachablePolicy {
+    /// The analysis fails and reports the unreachable vertices
+    #[default]
+    Error,
+    /// The unreachable vertices are left out of the statistics
+    Exclude,
+    /// Every unreachable vertex without parents is treated as the root of a separate component.
+    /// The depth of an unreachable vertex is measured from the nearest root of its component,
+    /// while the vertices reachable from the roots keep their depth from them.
+    SeparateComponents,
+}
+
 // Graph is a loosely coupled abstraction over the functions that returns statistical data about the graph
 pub struct Graph {
     pub graph: Vec<VertexWithStats>,
+    /// Empty until the graph is walked
+    inbounds: Adjacency,
+    /// The genesis vertices, [`ROOT_ID`] by default
+    roots: Vec<Id>,
+    unreachable_policy: UnreachablePolicy,
+    unreachable: Vec<Id>,
+    /// `None` until the graph is analyzed
+    running_stats: Option<RunningStats>,
 }
 
 impl Graph {
@@ -19,48 +88,377 @@ impl Graph {
     pub fn new(vertices: Vec<Vertex>) -> Self {
         Graph {
             graph: vertices.into_iter().map(VertexWithStats::from).collect(),
+            inbounds: Default::default(),
+            roots: vec![ROOT_ID],
+            unreachable_policy: Default::default(),
+            unreachable: Default::default(),
+            running_stats: None,
+        }
+    }
+
+    /// Sets the genesis vertices (e.g. the vertices of a snapshot) from which the graph is
+    /// walked. The root depth of a vertex is the shortest path to the nearest of them. The roots
+    /// that aren't in the graph yet are skipped until they're appended with
+    /// [`Graph::push_vertex`]. If the roots don't include the vertex `1`, which isn't stored
+    /// in the database, it's only analyzed if it's reached, and it's never reported as
+    /// unreachable.
+    pub fn with_roots(mut self, roots: Vec<Id>) -> Self {
+        self.roots = roots;
+        self
+    }
+
+    /// Returns IDs of the genesis vertices
+    pub fn roots(&self) -> &[Id] {
+        &self.roots
+    }
+
+    /// Sets the policy for the vertices unreachable from the root
+    pub fn with_unreachable_policy(mut self, policy: UnreachablePolicy) -> Self {
+        self.unreachable_policy = policy;
+        self
+    }
+
+    /// Returns IDs of the vertices that weren't reachable from the root during the last analysis
+    pub fn unreachable_vertices(&self) -> &[Id] {
+        &self.unreachable
+    }
+
+    /// Validates the structure of the graph: cycles, forward references, self-loops and
+    /// references to non-existing vertices
+    pub fn validate(&self) -> ValidationReport {
+        validation::validate(&self.graph)
+    }
+
+    /// Validates that no vertex is older than its parents and optionally that the timestamps
+    /// don't decrease in the order of the database
+    pub fn validate_timestamps(&self, check_file_order: bool) -> Vec<TimestampViolation> {
+        validation::validate_timestamps(&self.graph, check_file_order)
+    }
+
+    /// Like [`Graph::validate_timestamps`], but checks only the vertex with the given ID, e.g.
+    /// the one appended with [`Graph::push_vertex`]. Returns no violations if it doesn't exist.
+    pub fn validate_vertex_timestamps(
+        &self,
+        id: Id,
+        check_file_order: bool,
+    ) -> Vec<TimestampViolation> {
+        match self.vertex(id) {
+            Some(_) => validation::validate_vertex_timestamps(&self.graph, id, check_file_order),
+            None => vec![],
         }
     }
 
+    /// Reserves the capacity for at least `additional` more vertices to be appended with
+    /// [`Graph::push_vertex`]
+    pub fn reserve(&mut self, additional: usize) {
+        self.graph.reserve(additional);
+    }
+
     /// Performs statistical analysis on the graph
-    pub fn walk_and_analyze(&mut self) -> Result<(), anyhow::Error> {
-        find_inward_references(&mut self.graph)?;
-        find_root_depth(&mut self.graph)?;
+    pub fn walk_and_analyze(&mut self) -> Result<(), LedgerError> {
+        self.analyze(&SEQUENTIAL)
+    }
+
+    /// Performs the same analysis as [`Graph::walk_and_analyze`] using all the threads of the
+    /// rayon thread pool. The results are identical.
+    #[cfg(feature = "parallel")]
+    pub fn walk_and_analyze_parallel(&mut self) -> Result<(), LedgerError> {
+        self.analyze(&PARALLEL)
+    }
+
+    fn analyze(&mut self, steps: &AnalysisSteps) -> Result<(), LedgerError> {
+        self.walk(steps)?;
+        self.running_stats = Some((steps.running_stats)(&self.graph, &self.inbounds));
         Ok(())
     }
 
-    /// Calculates the avg number of inbound references per node
+    fn walk(&mut self, steps: &AnalysisSteps) -> Result<(), LedgerError> {
+        if self.roots.is_empty() {
+            return Err(LedgerError::NoRoots);
+        }
+        self.inbounds = (steps.find_inward_references)(&self.graph)?;
+        let roots: Vec<Id> = self
+            .roots
+            .iter()
+            .copied()
+            .filter(|id| *id <= self.graph.len())
+            .collect();
+        (steps.find_depth_from)(&mut self.graph, &self.inbounds, &roots)?;
+        let unreached = (steps.find_unreachable_vertices)(&self.graph);
+        // the root that isn't stored in the database is a placeholder if other roots are set
+        self.unreachable = unreached
+            .iter()
+            .copied()
+            .filter(|id| *id != ROOT_ID || self.roots.contains(&ROOT_ID))
+            .collect();
+        if self.unreachable.is_empty() {
+            return Ok(());
+        }
+
+        match self.unreachable_policy {
+            UnreachablePolicy::Error => {
+                Err(LedgerError::UnreachableVertices(self.unreachable.clone()))
+            }
+            UnreachablePolicy::Exclude => Ok(()),
+            UnreachablePolicy::SeparateComponents => {
+                let component_roots: Vec<Id> = unreached
+                    .into_iter()
+                    .filter(|id| self.graph[id - 1].vertex.parents.is_empty())
+                    .collect();
+                (steps.find_depth_from)(&mut self.graph, &self.inbounds, &component_roots)?;
+
+                // only the cyclic parts of the graph have no parentless vertex to start from
+                let left_over = (steps.find_unreachable_vertices)(&self.graph);
+                if !left_over.is_empty() {
+                    return Err(LedgerError::UnreachableVertices(left_over));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Appends the vertex to the analyzed graph and updates the statistics without walking the
+    /// whole graph again. Returns the ID of the vertex. The graph is analyzed first if it
+    /// hasn't been yet.
+    ///
+    /// The parents must already be in the graph, so the graph stays acyclic. The vertex with the
+    /// ID of one of the roots becomes a root. The vertex unreachable from the roots is treated
+    /// according to the [`UnreachablePolicy`], and with [`UnreachablePolicy::Error`] it isn't
+    /// appended. The cumulative weight isn't updated.
+    pub fn push_vertex(&mut self, vertex: Vertex) -> Result<Id, LedgerError> {
+        if self.running_stats.is_none() {
+            self.walk_and_analyze()?;
+        }
+        let id = self.graph.len() + 1;
+        let parents = vertex.parents;
+        for parent in parents.iter().copied() {
+            check_valid_id(parent, self.graph.len())?;
+        }
+
+        let mut vertex = VertexWithStats::from(vertex);
+        // the IDs are appended in order, so the unreachable vertices stay sorted
+        let from_roots = |parent: &Id| {
+            self.graph[parent - 1].visited && self.unreachable.binary_search(parent).is_err()
+        };
+        let min_depth = |only_from_roots: bool| {
+            parents
+                .iter()
+                .filter(|parent| self.graph[*parent - 1].visited)
+                .filter(|parent| !only_from_roots || from_roots(parent))
+                .map(|parent| self.graph[parent - 1].root_depth)
+                .min()
+        };
+        // the new vertex has no approvers, so its shortest path to the root leads via a parent.
+        // Like in the walk, the depths from the roots are preferred to the ones from the roots of
+        // separate components.
+        let parent_depth = min_depth(true).or_else(|| min_depth(false));
+        let is_root = self.roots.contains(&id);
+        let reachable = is_root || parents.iter().any(from_roots);
+        if !reachable {
+            if self.unreachable_policy == UnreachablePolicy::Error {
+                return Err(LedgerError::UnreachableVertices(vec![id]));
+            }
+            self.unreachable.push(id);
+        }
+        match parent_depth {
+            _ if is_root => {
+                vertex.visited = true;
+                vertex.root_depth = 0;
+            }
+            Some(depth) => {
+                vertex.visited = true;
+                vertex.root_depth = depth + 1;
+            }
+            None if self.unreachable_policy == UnreachablePolicy::SeparateComponents => {
+                // the vertex without parents is the root of a separate component
+                vertex.visited = true;
+                vertex.root_depth = 0;
+            }
+            None => {}
+        }
+
+        let running_stats = self.running_stats.as_mut().expect("the graph is analyzed");
+        self.inbounds.push_vertex();
+        for parent in parents.iter().copied() {
+            if self.graph[parent - 1].visited {
+                let approvers = self.inbounds.count(parent);
+                running_stats.add_inbound(&self.graph[parent - 1], approvers);
+            }
+            self.inbounds.push_approver(parent, id);
+        }
+        if vertex.visited {
+            running_stats.add_reached(&vertex, 0);
+        }
+        self.graph.push(vertex);
+        Ok(id)
+    }
+
+    /// Finds the cumulative weight of every vertex. It's a separate step from
+    /// [`Graph::walk_and_analyze`] as it's much more expensive, and it must be called after it.
+    pub fn analyze_cumulative_weight(&mut self) -> Result<(), LedgerError> {
+        cumulative_weight::find_cumulative_weight(&mut self.graph, &self.inbounds)
+    }
+
+    /// Returns the IDs of the vertices ordered so that every parent precedes its approvers, or
+    /// the error if the graph has a cycle. It must be called after [`Graph::walk_and_analyze`].
+    pub fn topological_order(&self) -> Result<Vec<Id>, LedgerError> {
+        let order = cumulative_weight::topological_order(&self.graph, &self.inbounds)?;
+        Ok(order.into_iter().map(|idx| idx + 1).collect())
+    }
+
+    /// Returns the vertex with its statistics or `None` if it doesn't exist
+    pub fn vertex(&self, id: Id) -> Option<&VertexWithStats> {
+        id.checked_sub(1).and_then(|idx| self.graph.get(idx))
+    }
+
+    /// Returns the inward references of all the vertices. They're empty until
+    /// [`Graph::walk_and_analyze`] is called.
+    pub fn inbounds(&self) -> &Adjacency {
+        &self.inbounds
+    }
+
+    /// Returns the iterator over the IDs of the vertices that directly approve the vertex,
+    /// ordered by ID. It must be called after [`Graph::walk_and_analyze`].
+    pub fn approvers(&self, id: Id) -> Approvers<'_> {
+        self.inbounds.approvers(id)
+    }
+
+    /// Returns the cumulative weight of the vertex or `None` if it doesn't exist. The weight is
+    /// 0 until [`Graph::analyze_cumulative_weight`] is called.
+    pub fn cumulative_weight(&self, id: Id) -> Option<usize> {
+        self.vertex(id).map(|vertex| vertex.cumulative_weight)
+    }
+
+    /// Classifies the vertices as confirmed, pending or at risk of being orphaned. Returns the ID
+    /// and the status of each vertex. With [`ConfirmationThreshold::Weight`] it must be called
+    /// after [`Graph::analyze_cumulative_weight`].
+    pub fn classify_confirmations(
+        &self,
+        config: &ConfirmationConfig,
+    ) -> Result<Vec<(Id, ConfirmationStatus)>, LedgerError> {
+        confirmation::classify_confirmations(&self.graph, &self.inbounds, config)
+    }
+
+    /// Returns the iterator over the IDs of the vertices that the vertex directly or indirectly
+    /// approves
+    pub fn past_cone(&self, id: Id) -> Result<Cone<'_>, LedgerError> {
+        cone::past_cone(&self.graph, id)
+    }
+
+    /// Returns the iterator over the IDs of the vertices that directly or indirectly approve
+    /// the vertex. It must be called after [`Graph::walk_and_analyze`].
+    pub fn future_cone(&self, id: Id) -> Result<Cone<'_>, LedgerError> {
+        cone::future_cone(&self.graph, &self.inbounds, id)
+    }
+
+    /// Returns all the statistics of the graph. It must be called after
+    /// [`Graph::walk_and_analyze`].
+    pub fn stats(&self) -> GraphStats {
+        // the placeholder of the root isn't part of the ledger
+        let placeholder = !self.roots.contains(&ROOT_ID)
+            && self.graph.first().is_some_and(|vertex| !vertex.visited);
+        GraphStats {
+            vertices: self.graph.len() - usize::from(placeholder),
+            unreachable_vertices: self.unreachable.len(),
+            avg_root_depth_per_node: self.calc_avg_root_depth_per_node(),
+            avg_nodes_per_root_depth: self.calc_avg_nodes_per_root_depth(),
+            avg_inbound_ref_per_node: self.calc_avg_inbound_ref_per_node(),
+            tips: match &self.running_stats {
+                Some(running_stats) => running_stats.tips(),
+                None => self.tips().len(),
+            },
+            tip_ratio: self.calc_tip_ratio(),
+            avg_tip_age: self.calc_avg_tip_age(),
+        }
+    }
+
+    /// Returns the statistics of the vertices nearest to each root, including the roots of the
+    /// separate components, ordered by the ID of the root. Unlike [`Graph::stats`] it walks the
+    /// whole graph. It must be called after [`Graph::walk_and_analyze`].
+    pub fn root_stats(&self) -> Vec<RootStats> {
+        roots::root_stats(&self.graph, &self.inbounds)
+    }
+
+    /// Calculates the avg number of inbound references per node. It's O(1) after the analysis.
     pub fn calc_avg_inbound_ref_per_node(&self) -> f64 {
-        calc_avg_inbound_ref_per_node(&self.graph)
+        match &self.running_stats {
+            Some(running_stats) => running_stats.avg_inbound_ref_per_node(),
+            None => calc_avg_inbound_ref_per_node(&self.graph, &self.inbounds),
+        }
     }
 
-    /// Calculates the avg number of nodes per root depth
+    /// Calculates the avg number of nodes per root depth. It's O(1) after the analysis.
     pub fn calc_avg_nodes_per_root_depth(&self) -> f64 {
-        calc_avg_nodes_per_root_depth(&self.graph)
+        match &self.running_stats {
+            Some(running_stats) => running_stats.avg_nodes_per_root_depth(),
+            None => calc_avg_nodes_per_root_depth(&self.graph),
+        }
     }
 
-    /// Calculates the avg root depth per node
+    /// Calculates the avg root depth per node. It's O(1) after the analysis.
     pub fn calc_avg_root_depth_per_node(&self) -> f64 {
-        calc_avg_root_depth_per_node(&self.graph)
+        match &self.running_stats {
+            Some(running_stats) => running_stats.avg_root_depth_per_node(),
+            None => calc_avg_root_depth_per_node(&self.graph),
+        }
+    }
+
+    /// Returns IDs of the tips: the vertices without inbound references
+    pub fn tips(&self) -> Vec<Id> {
+        tips::find_tips(&self.graph, &self.inbounds)
+    }
+
+    /// Calculates the ratio of tips to all vertices. It's O(1) after the analysis.
+    pub fn calc_tip_ratio(&self) -> f64 {
+        match &self.running_stats {
+            Some(running_stats) => running_stats.tip_ratio(),
+            None => tips::calc_tip_ratio(&self.graph, &self.inbounds),
+        }
+    }
+
+    /// Calculates the avg age of tips relative to the latest timestamp. It's O(1) after the
+    /// analysis.
+    pub fn calc_avg_tip_age(&self) -> f64 {
+        match &self.running_stats {
+            Some(running_stats) => running_stats.avg_tip_age(),
+            None => tips::calc_avg_tip_age(&self.graph, &self.inbounds),
+        }
     }
 }
 
-fn calc_avg_inbound_ref_per_node(graph: &[VertexWithStats]) -> f64 {
+/// Returns the vertices that have been reached during the analysis. The unreachable ones are
+/// excluded from the statistics.
+fn reached(graph: &[VertexWithStats]) -> impl Iterator<Item = &VertexWithStats> {
+    graph.iter().filter(|vertex| vertex.visited)
+}
+
+/// Same as [`reached`] but along with the IDs of the vertices
+fn reached_with_ids(graph: &[VertexWithStats]) -> impl Iterator<Item = (Id, &VertexWithStats)> {
     graph
         .iter()
-        .map(|vertex| vertex.inbounds.len())
+        .enumerate()
+        .filter(|(_, vertex)| vertex.visited)
+        .map(|(idx, vertex)| (idx + 1, vertex))
+}
+
+fn calc_avg_inbound_ref_per_node(graph: &[VertexWithStats], inbounds: &Adjacency) -> f64 {
+    reached_with_ids(graph)
+        .map(|(id, _)| inbounds.count(id))
         .sum::<usize>() as f64
-        / graph.len() as f64
+        / reached(graph).count() as f64
 }
 
 fn calc_avg_root_depth_per_node(graph: &[VertexWithStats]) -> f64 {
-    graph.iter().map(|vertex| vertex.root_depth).sum::<usize>() as f64 / graph.len() as f64
+    reached(graph)
+        .map(|vertex| vertex.root_depth)
+        .sum::<usize>() as f64
+        / reached(graph).count() as f64
 }
 
 fn calc_avg_nodes_per_root_depth(graph: &[VertexWithStats]) -> f64 {
     let mut depths_cnt = vec![0; graph.len()];
 
-    for vertex in graph {
+    for vertex in reached(graph) {
         depths_cnt[vertex.root_depth] += 1
     }
 
@@ -76,42 +474,33 @@ fn calc_avg_nodes_per_root_depth(graph: &[VertexWithStats]) -> f64 {
 }
 
 /// finds the inward references for all vertices in graph
-pub fn find_inward_references(graph: &mut [VertexWithStats]) -> Result<(), anyhow::Error> {
+pub fn find_inward_references(graph: &[VertexWithStats]) -> Result<Adjacency, LedgerError> {
     if graph.is_empty() {
-        bail!("the graph cannot be empty")
-    }
-    let max_id = graph.len();
-
-    for i in 0..graph.len() {
-        let current_id = i + 1;
-
-        if let Some(ref id) = graph[i].vertex.left {
-            check_valid_id(*id, max_id)?;
-
-            let left_idx = id - 1;
-            graph[left_idx].inbounds.push(current_id)
-        }
-
-        if let Some(ref id) = graph[i].vertex.right {
-            check_valid_id(*id, max_id)?;
-
-            let right_idx = id - 1;
-            graph[right_idx].inbounds.push(current_id)
-        }
+        return Err(LedgerError::EmptyGraph);
     }
+    Adjacency::build(graph)
+}
 
-    Ok(())
+/// finds the depth (the shortest path to the root) for each vertex using its inward references
+pub fn find_root_depth(
+    graph: &mut [VertexWithStats],
+    inbounds: &Adjacency,
+) -> Result<(), LedgerError> {
+    find_depth_from(graph, inbounds, &[ROOT_ID])
 }
 
-/// finds the depth (the shortest path to the root) for each vertex. **Before finding
-/// the root depth you must find find the inward references**.
-pub fn find_root_depth(graph: &mut [VertexWithStats]) -> Result<(), anyhow::Error> {
+/// finds the depth (the shortest path to the nearest of `roots`) for each vertex reachable
+/// from the `roots`. The vertices visited before are skipped.
+fn find_depth_from(
+    graph: &mut [VertexWithStats],
+    inbounds: &Adjacency,
+    roots: &[Id],
+) -> Result<(), LedgerError> {
     if graph.is_empty() {
-        bail!("the graph cannot be empty")
+        return Err(LedgerError::EmptyGraph);
     }
     let max_id = graph.len();
-    let mut queue: VecDeque<(PathLength, Id)> = VecDeque::new();
-    queue.push_front((0, 1));
+    let mut queue: VecDeque<(PathLength, Id)> = roots.iter().map(|id| (0, *id)).collect();
 
     while let Some((path_len, vertex_id)) = queue.pop_front() {
         check_valid_id(vertex_id, max_id)?;
@@ -127,104 +516,102 @@ pub fn find_root_depth(graph: &mut [VertexWithStats]) -> Result<(), anyhow::Erro
             graph[vertex_idx].root_depth = path_len
         }
 
-        queue.extend(
-            graph[vertex_idx]
-                .inbounds
-                .iter()
-                .map(|id| (path_len + 1, *id)),
-        );
+        queue.extend(inbounds.approvers(vertex_id).map(|id| (path_len + 1, id)));
         trace!("left to visit: {:?}", queue);
     }
     Ok(())
 }
 
+/// returns IDs of the vertices that haven't been visited while finding the root depth
+pub fn find_unreachable_vertices(graph: &[VertexWithStats]) -> Vec<Id> {
+    graph
+        .iter()
+        .enumerate()
+        .filter(|(_, vertex)| !vertex.visited)
+        .map(|(idx, _)| idx + 1)
+        .collect()
+}
+
 #[inline]
-fn check_valid_id(id: usize, max_id: usize) -> Result<(), anyhow::Error> {
-    if id > max_id {
-        bail!("vertex with ID {id} doesn't exist. Max number is {max_id}",)
-    }
-    if id == 0 {
-        bail!("the graph cannot have the vertex with ID 0. The minimum is 1")
+fn check_valid_id(id: usize, max_id: usize) -> Result<(), LedgerError> {
+    if id > max_id || id == 0 {
+        return Err(LedgerError::InvalidVertexId { id, max_id });
     }
     Ok(())
 }
 
 #[cfg(test)]
 mod test {
-    use crate::vertex::Vertex;
+    use crate::{
+        error::LedgerError,
+        test_util::{vertex, vertex_at},
+        vertex::{Parents, Vertex},
+    };
 
-    use super::{find_inward_references, find_root_depth, vertex_with_stats::VertexWithStats};
+    use super::{
+        find_inward_references, find_root_depth, find_unreachable_vertices,
+        vertex_with_stats::VertexWithStats, Adjacency, Graph, Id, UnreachablePolicy,
+    };
 
     #[test]
     fn test_find_inward_references() {
-        let mut graph = vec![
+        let graph = vec![
             VertexWithStats {
                 ..Default::default()
             },
             VertexWithStats {
                 vertex: Vertex {
-                    left: Some(1),
-                    right: None,
+                    parents: Parents::from([1]),
                     ..Default::default()
                 },
                 ..Default::default()
             },
         ];
-        find_inward_references(&mut graph).expect("shouldn't return error");
-        assert_eq!(vec![2], graph[0].inbounds);
+        let inbounds = find_inward_references(&graph).expect("shouldn't return error");
+        assert_eq!(vec![2], inbounds.approvers(1).collect::<Vec<_>>());
     }
 
     #[test]
     fn test_find_inward_references_invalid_id() {
-        let mut graph = vec![
+        let graph = vec![
             VertexWithStats {
                 ..Default::default()
             },
             VertexWithStats {
                 vertex: Vertex {
-                    left: Some(3),
-                    right: None,
+                    parents: Parents::from([3]),
                     ..Default::default()
                 },
                 ..Default::default()
             },
         ];
-        let err = find_inward_references(&mut graph).expect_err("should return error");
+        let err = find_inward_references(&graph).expect_err("should return error");
         assert!(
             err.to_string().contains("vertex with ID 3 doesn't exist"),
             "{err}"
-        )
+        );
+        assert!(matches!(
+            err,
+            LedgerError::InvalidVertexId { id: 3, max_id: 2 }
+        ));
     }
 
     #[test]
     fn test_find_inward_references_empty_graph() {
-        let mut graph: Vec<VertexWithStats> = vec![];
-        let err = find_inward_references(&mut graph).expect_err("should return error");
+        let graph: Vec<VertexWithStats> = vec![];
+        let err = find_inward_references(&graph).expect_err("should return error");
         assert!(
             err.to_string().contains("the graph cannot be empty"),
             "{err}"
-        )
+        );
+        assert!(matches!(err, LedgerError::EmptyGraph));
     }
 
     #[test]
     fn test_find_root_depth() {
-        let mut graph = vec![
-            VertexWithStats {
-                inbounds: vec![2, 3],
-                ..Default::default()
-            },
-            VertexWithStats {
-                ..Default::default()
-            },
-            VertexWithStats {
-                inbounds: vec![4],
-                ..Default::default()
-            },
-            VertexWithStats {
-                ..Default::default()
-            },
-        ];
-        find_root_depth(&mut graph).expect("shouldn't return error");
+        let mut graph: Vec<VertexWithStats> = (0..4).map(|_| Default::default()).collect();
+        let inbounds = Adjacency::from_approvers([vec![2, 3], vec![], vec![4], vec![]]);
+        find_root_depth(&mut graph, &inbounds).expect("shouldn't return error");
         assert!(graph[0].visited);
         assert!(graph[1].visited);
         assert!(graph[2].visited);
@@ -238,16 +625,9 @@ mod test {
 
     #[test]
     fn test_find_root_depth_invalid_vertex_id() {
-        let mut graph = vec![
-            VertexWithStats {
-                inbounds: vec![3],
-                ..Default::default()
-            },
-            VertexWithStats {
-                ..Default::default()
-            },
-        ];
-        let err = find_root_depth(&mut graph).expect_err("should no return error");
+        let mut graph: Vec<VertexWithStats> = (0..2).map(|_| Default::default()).collect();
+        let inbounds = Adjacency::from_approvers([vec![3], vec![]]);
+        let err = find_root_depth(&mut graph, &inbounds).expect_err("should no return error");
         assert!(
             err.to_string().contains("vertex with ID 3 doesn't exist"),
             "{err}"
@@ -256,16 +636,9 @@ mod test {
 
     #[test]
     fn test_find_root_depth_invalid_vertex_id_is_zero() {
-        let mut graph = vec![
-            VertexWithStats {
-                inbounds: vec![0],
-                ..Default::default()
-            },
-            VertexWithStats {
-                ..Default::default()
-            },
-        ];
-        let err = find_root_depth(&mut graph).expect_err("should no return error");
+        let mut graph: Vec<VertexWithStats> = (0..2).map(|_| Default::default()).collect();
+        let inbounds = Adjacency::from_approvers([vec![0], vec![]]);
+        let err = find_root_depth(&mut graph, &inbounds).expect_err("should no return error");
         assert!(
             err.to_string()
                 .contains("the graph cannot have the vertex with ID 0"),
@@ -273,10 +646,297 @@ mod test {
         )
     }
 
+    #[test]
+    fn test_find_unreachable_vertices() {
+        let mut graph: Vec<VertexWithStats> = (0..3).map(|_| Default::default()).collect();
+        let inbounds = Adjacency::from_approvers([vec![2], vec![], vec![]]);
+        find_root_depth(&mut graph, &inbounds).expect("shouldn't return error");
+        assert_eq!(vec![3], find_unreachable_vertices(&graph));
+    }
+
+    fn graph_with_orphans() -> Vec<Vertex> {
+        vec![
+            vertex(&[]),
+            vertex(&[1, 1]),
+            vertex(&[]),
+            vertex(&[3]),
+            vertex(&[4, 3]),
+        ]
+    }
+
+    #[test]
+    fn test_unreachable_policy_error() {
+        let mut graph = Graph::new(graph_with_orphans());
+        let err = graph.walk_and_analyze().expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::UnreachableVertices(ref ids) if ids == &[3, 4, 5]),
+            "{err}"
+        );
+        assert_eq!(&[3, 4, 5], graph.unreachable_vertices());
+    }
+
+    #[test]
+    fn test_unreachable_policy_exclude() {
+        let mut graph =
+            Graph::new(graph_with_orphans()).with_unreachable_policy(UnreachablePolicy::Exclude);
+        graph.walk_and_analyze().expect("shouldn't return error");
+
+        assert_eq!(&[3, 4, 5], graph.unreachable_vertices());
+        assert_eq!(1.0, graph.calc_avg_inbound_ref_per_node());
+        assert_eq!(0.5, graph.calc_avg_root_depth_per_node());
+        assert_eq!(1.0, graph.calc_avg_nodes_per_root_depth());
+    }
+
+    #[test]
+    fn test_unreachable_policy_separate_components() {
+        let mut graph = Graph::new(graph_with_orphans())
+            .with_unreachable_policy(UnreachablePolicy::SeparateComponents);
+        graph.walk_and_analyze().expect("shouldn't return error");
+
+        let depths: Vec<usize> = graph.graph.iter().map(|v| v.root_depth).collect();
+        assert_eq!(vec![0, 1, 0, 1, 1], depths);
+        assert_eq!(3.0, graph.calc_avg_nodes_per_root_depth());
+    }
+
+    #[test]
+    fn test_multiple_roots() {
+        let mut graph = Graph::new(graph_with_orphans()).with_roots(vec![3, 1]);
+        graph.walk_and_analyze().expect("shouldn't return error");
+
+        let depths: Vec<usize> = graph.graph.iter().map(|v| v.root_depth).collect();
+        assert_eq!(vec![0, 1, 0, 1, 1], depths);
+        assert!(graph.unreachable_vertices().is_empty());
+        let roots: Vec<(Id, usize)> = graph
+            .root_stats()
+            .iter()
+            .map(|root| (root.root, root.stats.vertices))
+            .collect();
+        assert_eq!(vec![(1, 2), (3, 3)], roots);
+
+        // the root appended later becomes the root of its future cone
+        let vertices = graph_with_orphans();
+        let mut pushed = Graph::new(vertices[..2].to_vec()).with_roots(vec![1, 3]);
+        for vertex in &vertices[2..] {
+            pushed.push_vertex(vertex.clone()).expect("valid vertex");
+        }
+        assert_eq!(graph.stats(), pushed.stats());
+        assert_eq!(graph.root_stats(), pushed.root_stats());
+
+        // the vertices in the past of the genesis aren't reachable from it, except for the
+        // vertex 1, which isn't stored
+        let mut graph = Graph::new(graph_with_orphans()).with_roots(vec![4]);
+        let err = graph.walk_and_analyze().expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::UnreachableVertices(ref ids) if ids == &[2, 3]),
+            "{err}"
+        );
+
+        let mut graph = Graph::new(graph_with_orphans()).with_roots(vec![]);
+        let err = graph.walk_and_analyze().expect_err("should return error");
+        assert!(matches!(err, LedgerError::NoRoots), "{err}");
+    }
+
+    #[test]
+    fn test_roots_without_first_vertex() {
+        // a snapshot with the genesis vertices 4 and 7, which doesn't use the vertex 1
+        let vertices = [
+            vertex(&[]),
+            vertex(&[4]),
+            vertex(&[2, 7]),
+            vertex(&[]),
+            vertex(&[4]),
+            vertex(&[3, 5]),
+            vertex(&[]),
+        ];
+        let mut graph = Graph::new(vertices.to_vec()).with_roots(vec![4, 7]);
+        graph.walk_and_analyze().expect("shouldn't return error");
+        assert!(graph.unreachable_vertices().is_empty());
+        assert!(!graph.graph[0].visited);
+        let depths: Vec<usize> = graph.graph[1..].iter().map(|v| v.root_depth).collect();
+        assert_eq!(vec![1, 1, 0, 1, 2, 0], depths);
+        assert_eq!(6, graph.stats().vertices);
+        let roots: Vec<Id> = graph.root_stats().iter().map(|root| root.root).collect();
+        assert_eq!(vec![4, 7], roots);
+
+        // the vertex approving only the vertex 1 isn't reached
+        let err = graph
+            .push_vertex(vertex(&[1]))
+            .expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::UnreachableVertices(ref ids) if ids == &[8]),
+            "{err}"
+        );
+
+        // the vertex 1 is still reported if it's approved by an unreachable vertex
+        let mut graph = Graph::new(vertices.to_vec())
+            .with_roots(vec![4, 7])
+            .with_unreachable_policy(UnreachablePolicy::Exclude);
+        graph.push_vertex(vertex(&[1])).expect("excluded vertex");
+        assert_eq!(&[8], graph.unreachable_vertices());
+    }
+
+    /// Checks that the statistics updated by [`Graph::push_vertex`] are the same as found by
+    /// the analysis of the whole graph
+    fn assert_same_stats(graph: &Graph, vertices: &[Vertex], policy: UnreachablePolicy) {
+        let mut analyzed = Graph::new(vertices.to_vec()).with_unreachable_policy(policy);
+        analyzed.walk_and_analyze().expect("shouldn't return error");
+        let depths = |graph: &Graph| -> Vec<usize> {
+            graph.graph.iter().map(|vertex| vertex.root_depth).collect()
+        };
+
+        assert_eq!(depths(&analyzed), depths(graph));
+        assert_eq!(
+            analyzed.unreachable_vertices(),
+            graph.unreachable_vertices()
+        );
+        let stats = analyzed.stats();
+        // the statistics are compared with the ones found by walking the graph
+        analyzed.running_stats = None;
+        let walked = analyzed.stats();
+        for stats in [stats, graph.stats()] {
+            assert_eq!(
+                walked.avg_root_depth_per_node.to_bits(),
+                stats.avg_root_depth_per_node.to_bits()
+            );
+            assert_eq!(
+                walked.avg_nodes_per_root_depth.to_bits(),
+                stats.avg_nodes_per_root_depth.to_bits()
+            );
+            assert_eq!(
+                walked.avg_inbound_ref_per_node.to_bits(),
+                stats.avg_inbound_ref_per_node.to_bits()
+            );
+            assert_eq!(walked.tips, stats.tips);
+            assert_eq!(walked.tip_ratio.to_bits(), stats.tip_ratio.to_bits());
+            assert_eq!(walked.avg_tip_age.to_bits(), stats.avg_tip_age.to_bits());
+        }
+    }
+
+    #[test]
+    fn test_push_vertex() {
+        let vertices = [
+            vertex_at(&[], 0),
+            vertex_at(&[1, 1], 1),
+            vertex_at(&[1, 2], 3),
+            vertex_at(&[2, 2], 2),
+            vertex_at(&[3, 4], 5),
+            vertex_at(&[4], 4),
+        ];
+        let mut graph = Graph::new(vertices[..1].to_vec());
+        for (idx, vertex) in vertices.iter().enumerate().skip(1) {
+            assert_eq!(
+                idx + 1,
+                graph.push_vertex(vertex.clone()).expect("valid vertex")
+            );
+            assert_same_stats(&graph, &vertices[..=idx], UnreachablePolicy::Error);
+        }
+        assert_eq!(vec![5, 6], graph.tips());
+    }
+
+    #[test]
+    fn test_push_vertex_many_parents() {
+        let vertices = [
+            vertex_at(&[], 0),
+            vertex_at(&[1, 1, 1], 1),
+            vertex_at(&[1, 2, 2], 3),
+            vertex_at(&[2], 2),
+            vertex_at(&[1, 3, 4, 4], 5),
+            vertex_at(&[1, 2, 3, 4, 5, 5, 4, 3], 6),
+        ];
+        let mut graph = Graph::new(vertices[..1].to_vec());
+        for (idx, vertex) in vertices.iter().enumerate().skip(1) {
+            graph.push_vertex(vertex.clone()).expect("valid vertex");
+            assert_same_stats(&graph, &vertices[..=idx], UnreachablePolicy::Error);
+        }
+        assert_eq!(vec![6], graph.tips());
+        // the repeated parents are counted like the two edges to the same parent
+        assert_eq!(4, graph.approvers(4).len());
+    }
+
+    #[test]
+    fn test_push_vertex_separate_components() {
+        // 5 is reachable from the root via 3 and from the separate component 4 directly
+        let vertices = [
+            vertex(&[]),
+            vertex(&[1]),
+            vertex(&[2]),
+            vertex(&[]),
+            vertex(&[3, 4]),
+        ];
+        let policy = UnreachablePolicy::SeparateComponents;
+        let mut graph = Graph::new(vertices[..1].to_vec()).with_unreachable_policy(policy);
+        for (idx, vertex) in vertices.iter().enumerate().skip(1) {
+            graph.push_vertex(vertex.clone()).expect("valid vertex");
+            assert_same_stats(&graph, &vertices[..=idx], policy);
+        }
+        assert_eq!(3, graph.graph[4].root_depth);
+    }
+
+    #[test]
+    fn test_push_vertex_validate_timestamps() {
+        let vertices = [
+            vertex_at(&[], 0),
+            vertex_at(&[1], 5),
+            vertex_at(&[1], 3),
+            vertex_at(&[2, 3], 4),
+        ];
+        let mut graph = Graph::new(vertices[..1].to_vec());
+        let mut violations = vec![];
+        for vertex in &vertices[1..] {
+            let id = graph.push_vertex(vertex.clone()).expect("valid vertex");
+            violations.extend(graph.validate_vertex_timestamps(id, true));
+        }
+        assert_eq!(2, violations.len(), "{violations:?}");
+        assert_eq!(graph.validate_timestamps(true), violations);
+        assert!(graph.validate_vertex_timestamps(5, true).is_empty());
+    }
+
+    #[test]
+    fn test_push_vertex_invalid_parent() {
+        let mut graph = Graph::new(vec![Vertex::default()]);
+        let err = graph
+            .push_vertex(Vertex {
+                parents: Parents::from([2]),
+                ..Default::default()
+            })
+            .expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidVertexId { id: 2, max_id: 1 }),
+            "{err}"
+        );
+        assert_eq!(1, graph.graph.len());
+    }
+
+    #[test]
+    fn test_push_vertex_unreachable() {
+        let vertices = graph_with_orphans();
+        for policy in [
+            UnreachablePolicy::Exclude,
+            UnreachablePolicy::SeparateComponents,
+        ] {
+            let mut graph = Graph::new(vertices[..1].to_vec()).with_unreachable_policy(policy);
+            for vertex in &vertices[1..] {
+                graph.push_vertex(vertex.clone()).expect("valid vertex");
+            }
+            assert_same_stats(&graph, &vertices, policy);
+        }
+
+        let mut graph = Graph::new(vertices[..2].to_vec());
+        let err = graph
+            .push_vertex(vertices[2].clone())
+            .expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::UnreachableVertices(ref ids) if ids == &[3]),
+            "{err}"
+        );
+        assert_eq!(2, graph.graph.len());
+    }
+
     #[test]
     fn test_find_root_depth_empty_graph() {
         let mut graph = vec![];
-        let err = find_root_depth(&mut graph).expect_err("should no return error");
+        let err =
+            find_root_depth(&mut graph, &Adjacency::default()).expect_err("should no return error");
         assert!(
             err.to_string().contains("the graph cannot be empty"),
             "{err}"
@@ -0,0 +1,294 @@
+//! The parallel versions of the analysis steps. They produce exactly the same results as the
+//! sequential ones: the inbound references are in the same order, and the statistics are
+//! reduced from integer sums, so the averages are bit-identical.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use super::{
+    adjacency::Adjacency, check_valid_id, running_stats::RunningStats,
+    vertex_with_stats::VertexWithStats, ROOT_ID,
+};
+use crate::error::LedgerError;
+
+type Id = usize;
+
+/// finds the inward references for all vertices in graph. Like the sequential version, the
+/// approvers are ordered by ID.
+pub fn find_inward_references(graph: &[VertexWithStats]) -> Result<Adjacency, LedgerError> {
+    if graph.is_empty() {
+        return Err(LedgerError::EmptyGraph);
+    }
+    let max_id = graph.len();
+
+    // the approvers are counted, and then placed in the flat array at the offsets of the parents
+    let counts: Vec<AtomicUsize> = (0..max_id)
+        .into_par_iter()
+        .map(|_| AtomicUsize::new(0))
+        .collect();
+    let counted: Result<(), LedgerError> = graph
+        .par_iter()
+        .flat_map_iter(VertexWithStats::parents)
+        .try_for_each(|parent| {
+            check_valid_id(parent, max_id)?;
+            counts[parent - 1].fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        });
+    if counted.is_err() {
+        // any invalid ID could be found first, so it's looked for again in order
+        let parent = graph
+            .par_iter()
+            .flat_map_iter(VertexWithStats::parents)
+            .find_first(|parent| check_valid_id(*parent, max_id).is_err())
+            .expect("the invalid ID was found");
+        return Err(LedgerError::InvalidVertexId { id: parent, max_id });
+    }
+    let mut offsets = Vec::with_capacity(max_id + 1);
+    offsets.push(0);
+    for count in &counts {
+        offsets.push(offsets[offsets.len() - 1] + count.load(Ordering::Relaxed));
+    }
+    let cursors: Vec<AtomicUsize> = offsets[..max_id]
+        .par_iter()
+        .map(|offset| AtomicUsize::new(*offset))
+        .collect();
+    let approvers: Vec<AtomicUsize> = (0..offsets[max_id])
+        .into_par_iter()
+        .map(|_| AtomicUsize::new(0))
+        .collect();
+    graph.par_iter().enumerate().for_each(|(idx, vertex)| {
+        for parent in vertex.parents() {
+            let position = cursors[parent - 1].fetch_add(1, Ordering::Relaxed);
+            approvers[position].store(idx + 1, Ordering::Relaxed);
+        }
+    });
+
+    let mut approvers: Vec<Id> = approvers
+        .into_par_iter()
+        .map(AtomicUsize::into_inner)
+        .collect();
+
+    // the approvers were placed in any order
+    let mut groups = Vec::with_capacity(max_id);
+    let mut rest = approvers.as_mut_slice();
+    for idx in 0..max_id {
+        let (group, tail) = std::mem::take(&mut rest).split_at_mut(offsets[idx + 1] - offsets[idx]);
+        groups.push(group);
+        rest = tail;
+    }
+    groups
+        .into_par_iter()
+        .for_each(|group| group.sort_unstable());
+    Ok(Adjacency::from_csr(offsets, approvers))
+}
+
+/// finds the depth (the shortest path to the root) for each vertex using its inward references,
+/// visiting the vertices at the same depth in parallel
+pub fn find_root_depth(
+    graph: &mut [VertexWithStats],
+    inbounds: &Adjacency,
+) -> Result<(), LedgerError> {
+    find_depth_from(graph, inbounds, &[ROOT_ID])
+}
+
+/// finds the depth (the shortest path to the nearest of `roots`) for each vertex reachable
+/// from the `roots`. The vertices visited before are skipped. If the graph references
+/// non-existing vertices, the error may name a different one than the sequential version.
+pub fn find_depth_from(
+    graph: &mut [VertexWithStats],
+    inbounds: &Adjacency,
+    roots: &[Id],
+) -> Result<(), LedgerError> {
+    if graph.is_empty() {
+        return Err(LedgerError::EmptyGraph);
+    }
+    let max_id = graph.len();
+    let visited: Vec<AtomicBool> = graph
+        .par_iter()
+        .map(|vertex| AtomicBool::new(vertex.visited))
+        .collect();
+    let depths: Vec<AtomicUsize> = graph
+        .par_iter()
+        .map(|vertex| AtomicUsize::new(vertex.root_depth))
+        .collect();
+    let visit = |id: Id, depth: usize| -> Result<bool, LedgerError> {
+        check_valid_id(id, max_id)?;
+        let newly_visited = visited[id - 1]
+            .compare_exchange(false, true, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok();
+        if newly_visited {
+            depths[id - 1].fetch_min(depth, Ordering::Relaxed);
+        }
+        Ok(newly_visited)
+    };
+
+    let mut frontier = vec![];
+    for root in roots {
+        if visit(*root, 0)? {
+            frontier.push(*root);
+        }
+    }
+    let mut next = vec![];
+    let mut depth = 0;
+    while !frontier.is_empty() {
+        depth += 1;
+        let approvers = |id: &Id| inbounds.approvers(*id);
+        // a ledger is long and narrow, so most of the frontiers are too small to split
+        if frontier.len() < MIN_PARALLEL_FRONTIER {
+            next.clear();
+            for id in frontier.iter().flat_map(approvers) {
+                if visit(id, depth)? {
+                    next.push(id);
+                }
+            }
+        } else {
+            next = frontier
+                .par_iter()
+                .flat_map_iter(approvers)
+                .filter_map(|id| match visit(id, depth) {
+                    Ok(true) => Some(Ok(id)),
+                    Ok(false) => None,
+                    Err(err) => Some(Err(err)),
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        std::mem::swap(&mut frontier, &mut next);
+    }
+
+    graph
+        .par_iter_mut()
+        .zip(visited.into_par_iter().zip(depths))
+        .for_each(|(vertex, (visited, depth))| {
+            vertex.visited = visited.into_inner();
+            vertex.root_depth = depth.into_inner();
+        });
+    Ok(())
+}
+
+/// returns IDs of the vertices that haven't been visited while finding the root depth
+pub fn find_unreachable_vertices(graph: &[VertexWithStats]) -> Vec<Id> {
+    graph
+        .par_iter()
+        .enumerate()
+        .filter(|(_, vertex)| !vertex.visited)
+        .map(|(idx, _)| idx + 1)
+        .collect()
+}
+
+pub(super) fn running_stats(graph: &[VertexWithStats], inbounds: &Adjacency) -> RunningStats {
+    graph
+        .par_chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(chunk, vertices)| {
+            RunningStats::for_vertices(vertices, chunk * CHUNK_SIZE + 1, inbounds)
+        })
+        .reduce(RunningStats::default, RunningStats::merge)
+}
+
+/// The number of vertices summed up by one task
+const CHUNK_SIZE: usize = 1 << 14;
+
+/// The smallest frontier of the BFS that is visited in parallel
+const MIN_PARALLEL_FRONTIER: usize = 1 << 10;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        generator::{generate_vertices, GeneratorConfig},
+        graph::{Graph, UnreachablePolicy},
+        test_util::vertex,
+        vertex::Vertex,
+    };
+
+    fn assert_same_analysis(vertices: Vec<Vertex>, policy: UnreachablePolicy) {
+        let mut sequential = Graph::new(vertices.clone()).with_unreachable_policy(policy);
+        let mut parallel = Graph::new(vertices).with_unreachable_policy(policy);
+        let sequential_result = sequential.walk_and_analyze().map_err(|err| err.to_string());
+        let parallel_result = parallel
+            .walk_and_analyze_parallel()
+            .map_err(|err| err.to_string());
+        assert_eq!(sequential_result, parallel_result);
+
+        assert_eq!(sequential.inbounds, parallel.inbounds);
+        for (sequential, parallel) in sequential.graph.iter().zip(&parallel.graph) {
+            assert_eq!(sequential.visited, parallel.visited);
+            assert_eq!(sequential.root_depth, parallel.root_depth);
+        }
+        assert_eq!(
+            sequential.unreachable_vertices(),
+            parallel.unreachable_vertices()
+        );
+        assert_eq!(sequential.running_stats, parallel.running_stats);
+        let (sequential, parallel) = (sequential.stats(), parallel.stats());
+        assert_eq!(
+            sequential.avg_nodes_per_root_depth.to_bits(),
+            parallel.avg_nodes_per_root_depth.to_bits()
+        );
+        assert_eq!(sequential, parallel);
+    }
+
+    #[test]
+    fn test_parallel_analysis_is_identical() {
+        for seed in 0..4 {
+            let vertices = generate_vertices(&GeneratorConfig {
+                vertices: 50_000,
+                window: 1 + seed as usize * 7,
+                max_time_step: 3,
+                seed,
+            });
+            assert_same_analysis(vertices, UnreachablePolicy::Error);
+        }
+    }
+
+    #[test]
+    fn test_parallel_analysis_unreachable() {
+        let vertices = vec![
+            vertex(&[]),
+            vertex(&[1, 1]),
+            vertex(&[]),
+            vertex(&[3]),
+            vertex(&[4, 3]),
+        ];
+        for policy in [
+            UnreachablePolicy::Error,
+            UnreachablePolicy::Exclude,
+            UnreachablePolicy::SeparateComponents,
+        ] {
+            assert_same_analysis(vertices.clone(), policy);
+        }
+    }
+
+    #[test]
+    fn test_parallel_analysis_many_parents() {
+        let vertices = vec![
+            vertex(&[]),
+            vertex(&[1, 1, 1]),
+            vertex(&[]),
+            vertex(&[3]),
+            vertex(&[4, 3, 2]),
+            vertex(&[1, 2, 3, 4, 5, 5, 4, 3]),
+        ];
+        for policy in [
+            UnreachablePolicy::Error,
+            UnreachablePolicy::Exclude,
+            UnreachablePolicy::SeparateComponents,
+        ] {
+            assert_same_analysis(vertices.clone(), policy);
+        }
+    }
+
+    #[test]
+    fn test_parallel_find_inward_references_invalid_id() {
+        let graph: Vec<VertexWithStats> = [&[][..], &[3], &[4]]
+            .into_iter()
+            .map(|parents| VertexWithStats::from(vertex(parents)))
+            .collect();
+        let err = find_inward_references(&graph).expect_err("should return error");
+        assert!(
+            matches!(err, LedgerError::InvalidVertexId { id: 4, max_id: 3 }),
+            "{err}"
+        );
+    }
+}
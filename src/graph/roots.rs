@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::{
+    adjacency::Adjacency, running_stats::RunningStats, stats::GraphStats,
+    vertex_with_stats::VertexWithStats,
+};
+
+type Id = usize;
+
+/// The statistics of the vertices nearest to one root. The vertices that are equally near to
+/// more roots are counted for the one with the lowest ID.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RootStats {
+    pub root: Id,
+    #[serde(flatten)]
+    pub stats: GraphStats,
+}
+
+/// Finds the nearest root of every reached vertex. The vertex at depth 0 is its own root, and
+/// any other vertex has the root of a parent one level closer to the roots. The vertices are
+/// visited in the order of depth, so the roots of the parents are known first.
+pub fn find_nearest_roots(graph: &[VertexWithStats]) -> Vec<Option<Id>> {
+    let mut order: Vec<Id> = (1..=graph.len())
+        .filter(|id| graph[id - 1].visited)
+        .collect();
+    order.sort_unstable_by_key(|id| graph[id - 1].root_depth);
+
+    let mut nearest = vec![None; graph.len()];
+    for id in order {
+        let vertex = &graph[id - 1];
+        nearest[id - 1] = if vertex.root_depth == 0 {
+            Some(id)
+        } else {
+            // taking the lowest ID makes the result independent of the order of the BFS
+            let root = vertex
+                .vertex
+                .parents
+                .iter()
+                .filter(|parent| {
+                    let parent = &graph[*parent - 1];
+                    parent.visited && parent.root_depth + 1 == vertex.root_depth
+                })
+                .filter_map(|parent| nearest[parent - 1])
+                .min();
+            Some(root.expect("the vertex was reached from a parent"))
+        };
+    }
+    nearest
+}
+
+/// Returns the statistics of the vertices nearest to each root, ordered by the ID of the root
+pub fn root_stats(graph: &[VertexWithStats], inbounds: &Adjacency) -> Vec<RootStats> {
+    let mut stats: BTreeMap<Id, RunningStats> = BTreeMap::new();
+    for (idx, root) in find_nearest_roots(graph).into_iter().enumerate() {
+        if let Some(root) = root {
+            stats
+                .entry(root)
+                .or_default()
+                .add_vertex(&graph[idx], inbounds.count(idx + 1));
+        }
+    }
+    stats
+        .into_iter()
+        .map(|(root, stats)| RootStats {
+            root,
+            stats: stats.to_stats(0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        graph::{find_depth_from, find_inward_references},
+        vertex::{Parents, Vertex},
+    };
+
+    use super::*;
+
+    fn analyzed(roots: &[Id]) -> (Vec<VertexWithStats>, Adjacency) {
+        // 1 and 3 have no parents, 4 is equally near to both of them
+        let mut graph: Vec<VertexWithStats> = [
+            Parents::default(),
+            Parents::from([1]),
+            Parents::default(),
+            Parents::from([3, 1]),
+            Parents::from([3]),
+            Parents::from([2, 4]),
+        ]
+        .into_iter()
+        .map(|parents| {
+            VertexWithStats::from(Vertex {
+                parents,
+                ..Default::default()
+            })
+        })
+        .collect();
+        let inbounds = find_inward_references(&graph).expect("valid graph");
+        find_depth_from(&mut graph, &inbounds, roots).expect("valid roots");
+        (graph, inbounds)
+    }
+
+    #[test]
+    fn test_find_nearest_roots() {
+        let (graph, _) = analyzed(&[3, 1]);
+        assert_eq!(
+            vec![Some(1), Some(1), Some(3), Some(1), Some(3), Some(1)],
+            find_nearest_roots(&graph)
+        );
+        let depths: Vec<usize> = graph.iter().map(|vertex| vertex.root_depth).collect();
+        assert_eq!(vec![0, 1, 0, 1, 1, 2], depths);
+
+        let (graph, _) = analyzed(&[3]);
+        assert_eq!(
+            vec![None, None, Some(3), Some(3), Some(3), Some(3)],
+            find_nearest_roots(&graph)
+        );
+    }
+
+    #[test]
+    fn test_root_stats() {
+        let (graph, inbounds) = analyzed(&[1, 3]);
+        let stats = root_stats(&graph, &inbounds);
+        assert_eq!(
+            vec![1, 3],
+            stats.iter().map(|stats| stats.root).collect::<Vec<_>>()
+        );
+        assert_eq!(4, stats[0].stats.vertices);
+        assert_eq!(2, stats[1].stats.vertices);
+        // 5 and 6 are the tips
+        assert_eq!(1, stats[0].stats.tips);
+        assert_eq!(1, stats[1].stats.tips);
+        assert_eq!(1.0, stats[0].stats.avg_root_depth_per_node);
+        assert_eq!(0.5, stats[1].stats.avg_root_depth_per_node);
+        assert_eq!(1.0, stats[0].stats.avg_inbound_ref_per_node);
+        assert_eq!(1.0, stats[1].stats.avg_inbound_ref_per_node);
+    }
+}
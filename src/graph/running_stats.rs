@@ -0,0 +1,141 @@
+use super::{adjacency::Adjacency, stats::GraphStats, vertex_with_stats::VertexWithStats};
+
+type Timestamp = u32;
+
+/// The sums behind the statistics of the reached vertices. They're found once after the
+/// analysis and then updated as the vertices are appended, so the statistics are O(1).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct RunningStats {
+    reached: usize,
+    inbound_refs: usize,
+    root_depth_sum: usize,
+    /// The number of vertices at each root depth
+    depth_counts: Vec<usize>,
+    /// The number of root depths above 0 with any vertex
+    depths: usize,
+    tips: usize,
+    tip_timestamp_sum: u64,
+    latest: Timestamp,
+}
+
+impl RunningStats {
+    /// Finds the sums for the analyzed graph
+    pub fn new(graph: &[VertexWithStats], inbounds: &Adjacency) -> Self {
+        Self::for_vertices(graph, 1, inbounds)
+    }
+
+    /// Finds the sums for the part of the analyzed graph starting at the vertex `first_id`
+    pub fn for_vertices(
+        vertices: &[VertexWithStats],
+        first_id: usize,
+        inbounds: &Adjacency,
+    ) -> Self {
+        let mut stats = RunningStats::default();
+        for (id, vertex) in (first_id..).zip(vertices) {
+            if vertex.visited {
+                stats.add_vertex(vertex, inbounds.count(id));
+            }
+        }
+        stats
+    }
+
+    /// Adds the reached vertex along with its inbound references
+    pub fn add_vertex(&mut self, vertex: &VertexWithStats, approvers: usize) {
+        self.add_reached(vertex, approvers);
+        self.inbound_refs += approvers;
+    }
+
+    /// Combines the sums found for two parts of the graph
+    #[cfg(feature = "parallel")]
+    pub fn merge(mut self, other: RunningStats) -> RunningStats {
+        if self.depth_counts.len() < other.depth_counts.len() {
+            self.depth_counts.resize(other.depth_counts.len(), 0);
+        }
+        for (count, other_count) in self.depth_counts.iter_mut().zip(&other.depth_counts) {
+            *count += other_count;
+        }
+        RunningStats {
+            reached: self.reached + other.reached,
+            inbound_refs: self.inbound_refs + other.inbound_refs,
+            root_depth_sum: self.root_depth_sum + other.root_depth_sum,
+            depths: self
+                .depth_counts
+                .iter()
+                .skip(1)
+                .filter(|count| **count > 0)
+                .count(),
+            depth_counts: self.depth_counts,
+            tips: self.tips + other.tips,
+            tip_timestamp_sum: self.tip_timestamp_sum + other.tip_timestamp_sum,
+            latest: self.latest.max(other.latest),
+        }
+    }
+
+    /// Adds the reached vertex with the number of its approvers. Its inbound references must be
+    /// added separately.
+    pub fn add_reached(&mut self, vertex: &VertexWithStats, approvers: usize) {
+        self.reached += 1;
+        self.root_depth_sum += vertex.root_depth;
+        if self.depth_counts.len() <= vertex.root_depth {
+            self.depth_counts.resize(vertex.root_depth + 1, 0);
+        }
+        self.depth_counts[vertex.root_depth] += 1;
+        // the depth of size 0 is skipped according to the requirements
+        if vertex.root_depth > 0 && self.depth_counts[vertex.root_depth] == 1 {
+            self.depths += 1;
+        }
+        self.latest = self.latest.max(vertex.vertex.timestamp);
+        if approvers == 0 {
+            self.tips += 1;
+            self.tip_timestamp_sum += vertex.vertex.timestamp as u64;
+        }
+    }
+
+    /// Adds the inbound reference to the reached vertex, which had `approvers` approvers before.
+    pub fn add_inbound(&mut self, vertex: &VertexWithStats, approvers: usize) {
+        self.inbound_refs += 1;
+        if approvers == 0 {
+            self.tips -= 1;
+            self.tip_timestamp_sum -= vertex.vertex.timestamp as u64;
+        }
+    }
+
+    pub fn avg_inbound_ref_per_node(&self) -> f64 {
+        self.inbound_refs as f64 / self.reached as f64
+    }
+
+    pub fn avg_root_depth_per_node(&self) -> f64 {
+        self.root_depth_sum as f64 / self.reached as f64
+    }
+
+    pub fn avg_nodes_per_root_depth(&self) -> f64 {
+        let at_root = self.depth_counts.first().copied().unwrap_or_default();
+        (self.reached - at_root) as f64 / self.depths as f64
+    }
+
+    pub fn tips(&self) -> usize {
+        self.tips
+    }
+
+    pub fn tip_ratio(&self) -> f64 {
+        self.tips as f64 / self.reached as f64
+    }
+
+    pub fn avg_tip_age(&self) -> f64 {
+        (self.tips as u64 * self.latest as u64 - self.tip_timestamp_sum) as f64 / self.tips as f64
+    }
+
+    /// Returns the statistics of the reached vertices and the given number of unreachable ones
+    pub fn to_stats(&self, unreachable_vertices: usize) -> GraphStats {
+        GraphStats {
+            vertices: self.reached + unreachable_vertices,
+            unreachable_vertices,
+            avg_root_depth_per_node: self.avg_root_depth_per_node(),
+            avg_nodes_per_root_depth: self.avg_nodes_per_root_depth(),
+            avg_inbound_ref_per_node: self.avg_inbound_ref_per_node(),
+            tips: self.tips(),
+            tip_ratio: self.tip_ratio(),
+            avg_tip_age: self.avg_tip_age(),
+        }
+    }
+}
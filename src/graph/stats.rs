@@ -0,0 +1,20 @@
+use serde::Serialize;
+
+/// The statistics of the analyzed graph. The root depth of a vertex is the shortest path to the
+/// nearest root. The unreachable vertices are counted only in `vertices` and
+/// `unreachable_vertices`, except with [`UnreachablePolicy::SeparateComponents`], where they're
+/// counted in all the statistics and their root depth is the shortest path to the nearest root
+/// of their component, a vertex without parents unreachable from the roots.
+///
+/// [`UnreachablePolicy::SeparateComponents`]: super::UnreachablePolicy::SeparateComponents
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphStats {
+    pub vertices: usize,
+    pub unreachable_vertices: usize,
+    pub avg_root_depth_per_node: f64,
+    pub avg_nodes_per_root_depth: f64,
+    pub avg_inbound_ref_per_node: f64,
+    pub tips: usize,
+    pub tip_ratio: f64,
+    pub avg_tip_age: f64,
+}
@@ -0,0 +1,70 @@
+use super::{adjacency::Adjacency, reached_with_ids, vertex_with_stats::VertexWithStats};
+
+type Id = usize;
+
+/// finds the tips: the vertices without inbound references, which new vertices attach to.
+/// **Before finding the tips you must find the inward references**.
+pub fn find_tips(graph: &[VertexWithStats], inbounds: &Adjacency) -> Vec<Id> {
+    reached_with_ids(graph)
+        .filter(|(id, _)| inbounds.count(*id) == 0)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+pub fn calc_tip_ratio(graph: &[VertexWithStats], inbounds: &Adjacency) -> f64 {
+    find_tips(graph, inbounds).len() as f64 / reached_with_ids(graph).count() as f64
+}
+
+/// The age of a tip is the difference between the latest timestamp in the graph and the
+/// timestamp of the tip
+pub fn calc_avg_tip_age(graph: &[VertexWithStats], inbounds: &Adjacency) -> f64 {
+    let latest = reached_with_ids(graph)
+        .map(|(_, vertex)| vertex.vertex.timestamp)
+        .max()
+        .unwrap_or_default();
+    let tips = find_tips(graph, inbounds);
+
+    tips.iter()
+        .map(|id| (latest - graph[id - 1].vertex.timestamp) as u64)
+        .sum::<u64>() as f64
+        / tips.len() as f64
+}
+
+#[cfg(test)]
+mod test {
+    use crate::vertex::Vertex;
+
+    use super::*;
+
+    fn graph() -> (Vec<VertexWithStats>, Adjacency) {
+        let vertex = |timestamp| VertexWithStats {
+            vertex: Vertex {
+                timestamp,
+                ..Default::default()
+            },
+            visited: true,
+            root_depth: 0,
+            cumulative_weight: 0,
+        };
+        let inbounds = Adjacency::from_approvers([vec![2, 3], vec![4], vec![], vec![]]);
+        (vec![vertex(0), vertex(1), vertex(4), vertex(2)], inbounds)
+    }
+
+    #[test]
+    fn test_find_tips() {
+        let (graph, inbounds) = graph();
+        assert_eq!(vec![3, 4], find_tips(&graph, &inbounds));
+    }
+
+    #[test]
+    fn test_calc_tip_ratio() {
+        let (graph, inbounds) = graph();
+        assert_eq!(0.5, calc_tip_ratio(&graph, &inbounds));
+    }
+
+    #[test]
+    fn test_calc_avg_tip_age() {
+        let (graph, inbounds) = graph();
+        assert_eq!(1.0, calc_avg_tip_age(&graph, &inbounds));
+    }
+}
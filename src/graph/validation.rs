@@ -0,0 +1,377 @@
+use std::fmt::Display;
+
+use super::vertex_with_stats::VertexWithStats;
+
+type Id = usize;
+type Timestamp = u32;
+
+/// An edge from the vertex to one of its parents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reference {
+    pub from: Id,
+    pub to: Id,
+}
+
+/// The result of the [`validate`] pass. Every issue found in the graph is reported, not only
+/// the first one.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Chains of vertex IDs forming a cycle. Each chain starts and ends with the same ID.
+    pub cycles: Vec<Vec<Id>>,
+    /// References to parents with a greater ID than the referencing vertex. The analysis supports
+    /// them, so they're only warnings and don't make the graph invalid.
+    pub forward_references: Vec<Reference>,
+    /// Vertices referencing themselves. The database rows referencing their own line are
+    /// converted to the missing edge by the parser, so only explicitly created edges end up here.
+    pub self_loops: Vec<Id>,
+    /// References to vertices that don't exist in the graph
+    pub invalid_references: Vec<Reference>,
+}
+
+impl ValidationReport {
+    /// Returns true if no errors were found. The forward references are only warnings.
+    pub fn is_valid(&self) -> bool {
+        self.cycles.is_empty() && self.self_loops.is_empty() && self.invalid_references.is_empty()
+    }
+
+    /// Returns true if there are neither errors nor warnings
+    pub fn is_empty(&self) -> bool {
+        self.is_valid() && self.forward_references.is_empty()
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "the graph is valid");
+        }
+        for cycle in &self.cycles {
+            let chain: Vec<String> = cycle.iter().map(Id::to_string).collect();
+            writeln!(f, "cycle: {}", chain.join(" -> "))?;
+        }
+        for reference in &self.forward_references {
+            writeln!(
+                f,
+                "warning: forward reference: vertex {} references vertex {}",
+                reference.from, reference.to
+            )?;
+        }
+        for id in &self.self_loops {
+            writeln!(f, "self-loop: vertex {id} references itself")?;
+        }
+        for reference in &self.invalid_references {
+            writeln!(
+                f,
+                "invalid reference: vertex {} references non-existing vertex {}",
+                reference.from, reference.to
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the graph is a DAG in which every vertex references only the existing vertices,
+/// and warns about the references to the later ones. Unlike the analysis, it doesn't stop at the
+/// first issue.
+pub fn validate(graph: &[VertexWithStats]) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let max_id = graph.len();
+
+    for (idx, vertex) in graph.iter().enumerate() {
+        let id = idx + 1;
+        for parent in vertex.parents() {
+            let reference = Reference {
+                from: id,
+                to: parent,
+            };
+            if parent == 0 || parent > max_id {
+                report.invalid_references.push(reference);
+            } else if parent == id {
+                report.self_loops.push(id);
+            } else if parent > id {
+                report.forward_references.push(reference);
+            }
+        }
+    }
+
+    report.cycles = find_cycles(graph);
+    report
+}
+
+/// A timestamp that breaks the ordering of the ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampViolation {
+    /// The line in the database, which is also the vertex ID
+    pub line: usize,
+    pub timestamp: Timestamp,
+    pub kind: TimestampViolationKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampViolationKind {
+    /// The vertex is older than the parent it references
+    OlderThanParent {
+        parent: Id,
+        parent_timestamp: Timestamp,
+    },
+    /// The vertex is older than the vertex on the previous line
+    OutOfOrder { previous_timestamp: Timestamp },
+}
+
+impl Display for TimestampViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            TimestampViolationKind::OlderThanParent {
+                parent,
+                parent_timestamp,
+            } => write!(
+                f,
+                "line {}: timestamp {} is earlier than the timestamp {parent_timestamp} of the parent {parent}",
+                self.line, self.timestamp
+            ),
+            TimestampViolationKind::OutOfOrder { previous_timestamp } => write!(
+                f,
+                "line {}: timestamp {} is earlier than the timestamp {previous_timestamp} on the previous line",
+                self.line, self.timestamp
+            ),
+        }
+    }
+}
+
+/// Checks that no vertex is older than its parents and, if `check_file_order` is set, that the
+/// timestamps don't decrease in the order of the database. The references to non-existing
+/// vertices and self-loops are skipped as they are reported by [`validate`].
+pub fn validate_timestamps(
+    graph: &[VertexWithStats],
+    check_file_order: bool,
+) -> Vec<TimestampViolation> {
+    let mut violations = vec![];
+    for id in 1..=graph.len() {
+        violations.extend(validate_vertex_timestamps(graph, id, check_file_order));
+    }
+    violations
+}
+
+/// Like [`validate_timestamps`], but checks only the vertex with the given ID
+pub fn validate_vertex_timestamps(
+    graph: &[VertexWithStats],
+    id: Id,
+    check_file_order: bool,
+) -> Vec<TimestampViolation> {
+    let mut violations = vec![];
+    let max_id = graph.len();
+    let idx = id - 1;
+    let vertex = &graph[idx];
+    let timestamp = vertex.vertex.timestamp;
+
+    if check_file_order && idx > 0 {
+        let previous_timestamp = graph[idx - 1].vertex.timestamp;
+        if timestamp < previous_timestamp {
+            violations.push(TimestampViolation {
+                line: id,
+                timestamp,
+                kind: TimestampViolationKind::OutOfOrder { previous_timestamp },
+            });
+        }
+    }
+
+    // several edges can point to the same parent
+    let all_parents = &vertex.vertex.parents;
+    for (position, parent) in vertex.parents().enumerate() {
+        if all_parents[..position].contains(&parent) {
+            continue;
+        }
+        if parent == 0 || parent > max_id || parent == id {
+            continue;
+        }
+        let parent_timestamp = graph[parent - 1].vertex.timestamp;
+        if timestamp < parent_timestamp {
+            violations.push(TimestampViolation {
+                line: id,
+                timestamp,
+                kind: TimestampViolationKind::OlderThanParent {
+                    parent,
+                    parent_timestamp,
+                },
+            });
+        }
+    }
+    violations
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    New,
+    OnStack,
+    Done,
+}
+
+/// Iterative DFS over the parent edges. The self-loops and invalid references are skipped as
+/// they are reported separately.
+fn find_cycles(graph: &[VertexWithStats]) -> Vec<Vec<Id>> {
+    let max_id = graph.len();
+    let mut state = vec![State::New; graph.len()];
+    let mut cycles = vec![];
+
+    for start in 0..graph.len() {
+        if state[start] != State::New {
+            continue;
+        }
+        // (vertex index, the number of parents already explored)
+        let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+        state[start] = State::OnStack;
+
+        while let Some((idx, explored)) = stack.last_mut() {
+            let idx = *idx;
+            let next_parent = graph[idx].parents().nth(*explored);
+            *explored += 1;
+
+            match next_parent {
+                None => {
+                    state[idx] = State::Done;
+                    stack.pop();
+                }
+                Some(parent) if parent == 0 || parent > max_id || parent == idx + 1 => {}
+                Some(parent) => {
+                    let parent_idx = parent - 1;
+                    match state[parent_idx] {
+                        State::New => {
+                            state[parent_idx] = State::OnStack;
+                            stack.push((parent_idx, 0));
+                        }
+                        State::OnStack => {
+                            let position = stack
+                                .iter()
+                                .position(|(i, _)| *i == parent_idx)
+                                .expect("vertex on stack");
+                            let mut cycle: Vec<Id> =
+                                stack[position..].iter().map(|(i, _)| i + 1).collect();
+                            cycle.push(parent);
+                            cycles.push(cycle);
+                        }
+                        State::Done => {}
+                    }
+                }
+            }
+        }
+    }
+
+    cycles
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test_util;
+
+    use super::*;
+
+    fn vertex(parents: &[Id]) -> VertexWithStats {
+        vertex_at(parents, 0)
+    }
+
+    fn vertex_at(parents: &[Id], timestamp: Timestamp) -> VertexWithStats {
+        VertexWithStats::from(test_util::vertex_at(parents, timestamp))
+    }
+
+    #[test]
+    fn test_validate_valid_graph() {
+        let graph = vec![vertex(&[]), vertex(&[1, 1]), vertex(&[1, 2])];
+        let report = validate(&graph);
+        assert!(report.is_valid(), "{report}");
+    }
+
+    #[test]
+    fn test_validate_cycle() {
+        let graph = vec![vertex(&[]), vertex(&[1, 4]), vertex(&[2]), vertex(&[3])];
+        let report = validate(&graph);
+        assert_eq!(vec![vec![2, 4, 3, 2]], report.cycles);
+        assert_eq!(
+            vec![Reference { from: 2, to: 4 }],
+            report.forward_references
+        );
+        assert!(report.self_loops.is_empty());
+    }
+
+    #[test]
+    fn test_validate_forward_reference_is_warning() {
+        let graph = vec![vertex(&[]), vertex(&[1, 3]), vertex(&[1])];
+        let report = validate(&graph);
+        assert!(report.is_valid(), "{report}");
+        assert!(!report.is_empty());
+        assert_eq!(
+            "warning: forward reference: vertex 2 references vertex 3\n",
+            report.to_string()
+        );
+    }
+
+    #[test]
+    fn test_validate_self_loop_and_invalid_reference() {
+        let graph = vec![vertex(&[]), vertex(&[2, 7])];
+        let report = validate(&graph);
+        assert_eq!(vec![2], report.self_loops);
+        assert_eq!(
+            vec![Reference { from: 2, to: 7 }],
+            report.invalid_references
+        );
+        assert!(report.cycles.is_empty());
+    }
+
+    #[test]
+    fn test_validate_timestamps_older_than_parent() {
+        let graph = vec![
+            vertex_at(&[], 0),
+            vertex_at(&[1, 1], 5),
+            vertex_at(&[1, 2], 3),
+        ];
+        let violations = validate_timestamps(&graph, false);
+        assert_eq!(
+            vec![TimestampViolation {
+                line: 3,
+                timestamp: 3,
+                kind: TimestampViolationKind::OlderThanParent {
+                    parent: 2,
+                    parent_timestamp: 5
+                }
+            }],
+            violations
+        );
+    }
+
+    #[test]
+    fn test_validate_timestamps_duplicate_parents() {
+        let graph = vec![
+            vertex_at(&[], 5),
+            vertex_at(&[1], 6),
+            vertex_at(&[1, 2, 1], 3),
+        ];
+        let violations = validate_timestamps(&graph, false);
+        let parents: Vec<Id> = violations
+            .iter()
+            .map(|violation| match violation.kind {
+                TimestampViolationKind::OlderThanParent { parent, .. } => parent,
+                TimestampViolationKind::OutOfOrder { .. } => unreachable!("file order not checked"),
+            })
+            .collect();
+        assert_eq!(vec![1, 2], parents);
+    }
+
+    #[test]
+    fn test_validate_timestamps_file_order() {
+        let graph = vec![
+            vertex_at(&[], 0),
+            vertex_at(&[1, 1], 5),
+            vertex_at(&[1, 1], 3),
+        ];
+        assert!(validate_timestamps(&graph, false).is_empty());
+        assert_eq!(
+            vec![TimestampViolation {
+                line: 3,
+                timestamp: 3,
+                kind: TimestampViolationKind::OutOfOrder {
+                    previous_timestamp: 5
+                }
+            }],
+            validate_timestamps(&graph, true)
+        );
+    }
+}
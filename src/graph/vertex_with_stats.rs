@@ -1,4 +1,5 @@
 use crate::vertex::Vertex;
+
 type Id = usize;
 
 /// [`Vertex`] that is additionally equipped with the metadata and allow calculating statistics
@@ -6,8 +7,17 @@ type Id = usize;
 pub struct VertexWithStats {
     pub vertex: Vertex,
     pub visited: bool,
-    pub inbounds: Vec<Id>,
     pub root_depth: usize,
+    /// 1 + the number of vertices that directly or indirectly approve the vertex. It's 0 until
+    /// the cumulative weight is found.
+    pub cumulative_weight: usize,
+}
+
+impl VertexWithStats {
+    /// Returns the iterator over the IDs of the parents
+    pub fn parents(&self) -> impl Iterator<Item = Id> + '_ {
+        self.vertex.parents.iter().copied()
+    }
 }
 
 impl Default for VertexWithStats {
@@ -15,8 +25,8 @@ impl Default for VertexWithStats {
         Self {
             vertex: Default::default(),
             visited: Default::default(),
-            inbounds: Default::default(),
             root_depth: usize::MAX,
+            cumulative_weight: 0,
         }
     }
 }
@@ -26,8 +36,8 @@ impl From<Vertex> for VertexWithStats {
         VertexWithStats {
             vertex,
             visited: false,
-            inbounds: Default::default(),
             root_depth: usize::MAX,
+            cumulative_weight: 0,
         }
     }
 }
@@ -0,0 +1,244 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use super::{build_vertices, edges, Edge, Id, Parent, Timestamp};
+use crate::{error::LedgerError, vertex::Vertex};
+
+const FORMAT: &str = "GraphML";
+const TIMESTAMP_KEY: &str = "timestamp";
+const PARENT_KEY: &str = "parent";
+
+/// Writes the vertices as GraphML. The nodes have the `timestamp` attribute and the edges the
+/// `parent` attribute (`left`, `right` or the position of the parent from 2).
+pub fn write_graphml<'a>(
+    vertices: impl IntoIterator<Item = &'a Vertex> + Clone,
+    mut writer: impl Write,
+) -> Result<(), LedgerError> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="{TIMESTAMP_KEY}" for="node" attr.name="{TIMESTAMP_KEY}" attr.type="long"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="{PARENT_KEY}" for="edge" attr.name="{PARENT_KEY}" attr.type="string"/>"#
+    )?;
+    writeln!(writer, r#"  <graph id="ledger" edgedefault="directed">"#)?;
+
+    for (idx, vertex) in vertices.clone().into_iter().enumerate() {
+        writeln!(
+            writer,
+            r#"    <node id="{}"><data key="{TIMESTAMP_KEY}">{}</data></node>"#,
+            idx + 1,
+            vertex.timestamp
+        )?;
+    }
+    for edge in edges(vertices) {
+        let parent = edge
+            .parent
+            .map(|parent| parent.to_string())
+            .unwrap_or_default();
+        writeln!(
+            writer,
+            r#"    <edge source="{}" target="{}"><data key="{PARENT_KEY}">{parent}</data></edge>"#,
+            edge.source, edge.target
+        )?;
+    }
+
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")?;
+    Ok(())
+}
+
+/// The element whose data is being read
+enum Element {
+    Node(Id, Option<Timestamp>),
+    Edge(Edge),
+}
+
+/// Reads the vertices from GraphML written by [`write_graphml`] or an external tool. The data
+/// keys are matched by their `attr.name`, and the `parent` attribute of the edges is optional.
+pub fn read_graphml(reader: impl BufRead) -> Result<Vec<Vertex>, LedgerError> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buffer = vec![];
+
+    // key ID -> attribute name
+    let mut keys: HashMap<String, String> = HashMap::new();
+    let mut nodes = vec![];
+    let mut edges = vec![];
+    let mut element: Option<Element> = None;
+    // the attribute name of the data being read
+    let mut data: Option<String> = None;
+
+    loop {
+        match reader.read_event_into(&mut buffer)? {
+            Event::Eof => break,
+            Event::Start(tag) | Event::Empty(tag) if tag.local_name().as_ref() == b"key" => {
+                let id = required_attribute(&tag, "id")?;
+                let name = attribute(&tag, "attr.name")?.unwrap_or_else(|| id.clone());
+                keys.insert(id, name);
+            }
+            Event::Start(tag) if tag.local_name().as_ref() == b"node" => {
+                element = Some(Element::Node(
+                    parse(&required_attribute(&tag, "id")?)?,
+                    None,
+                ));
+            }
+            Event::Empty(tag) if tag.local_name().as_ref() == b"node" => {
+                nodes.push((parse(&required_attribute(&tag, "id")?)?, 0));
+            }
+            Event::Start(tag) if tag.local_name().as_ref() == b"edge" => {
+                element = Some(Element::Edge(parse_edge(&tag)?));
+            }
+            Event::Empty(tag) if tag.local_name().as_ref() == b"edge" => {
+                edges.push(parse_edge(&tag)?);
+            }
+            Event::Start(tag) if tag.local_name().as_ref() == b"data" => {
+                let key = required_attribute(&tag, "key")?;
+                data = Some(keys.get(&key).cloned().unwrap_or(key));
+            }
+            Event::Text(text) => {
+                let text = text.unescape()?;
+                match (&mut element, data.as_deref()) {
+                    (Some(Element::Node(_, timestamp)), Some(TIMESTAMP_KEY)) => {
+                        *timestamp = Some(parse(text.trim())?)
+                    }
+                    (Some(Element::Edge(edge)), Some(PARENT_KEY)) => {
+                        edge.parent = Parent::from_str(text.trim());
+                        if edge.parent.is_none() {
+                            return Err(invalid(format!("unknown parent label '{text}'")));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(tag) => match tag.local_name().as_ref() {
+                b"data" => data = None,
+                b"node" | b"edge" => match element.take() {
+                    Some(Element::Node(id, timestamp)) => {
+                        nodes.push((id, timestamp.unwrap_or_default()))
+                    }
+                    Some(Element::Edge(edge)) => edges.push(edge),
+                    None => {}
+                },
+                _ => {}
+            },
+            _ => {}
+        }
+        buffer.clear();
+    }
+
+    build_vertices(FORMAT, nodes, edges)
+}
+
+fn parse_edge(tag: &BytesStart) -> Result<Edge, LedgerError> {
+    Ok(Edge {
+        source: parse(&required_attribute(tag, "source")?)?,
+        target: parse(&required_attribute(tag, "target")?)?,
+        parent: None,
+    })
+}
+
+fn attribute(tag: &BytesStart, name: &str) -> Result<Option<String>, LedgerError> {
+    match tag.try_get_attribute(name)? {
+        Some(attribute) => Ok(Some(attribute.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+fn required_attribute(tag: &BytesStart, name: &str) -> Result<String, LedgerError> {
+    attribute(tag, name)?.ok_or_else(|| {
+        invalid(format!(
+            "element '{}' has no attribute '{name}'",
+            String::from_utf8_lossy(tag.local_name().as_ref())
+        ))
+    })
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, LedgerError> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("'{value}' is not a valid number")))
+}
+
+fn invalid(reason: String) -> LedgerError {
+    LedgerError::InvalidDocument {
+        format: FORMAT,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vertex::Parents;
+
+    fn vertices() -> Vec<Vertex> {
+        vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([1, 1]),
+                timestamp: 3,
+            },
+            Vertex {
+                parents: Parents::from([2]),
+                timestamp: 7,
+            },
+            Vertex {
+                parents: Parents::from([3, 1, 2]),
+                timestamp: 8,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_graphml_round_trip() {
+        let mut output = vec![];
+        write_graphml(&vertices(), &mut output).expect("writing to vector");
+        let read = read_graphml(output.as_slice()).expect("valid document");
+        assert_eq!(vertices(), read);
+    }
+
+    #[test]
+    fn test_read_graphml_external() {
+        let document = r#"<?xml version="1.0"?>
+            <graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+              <key id="d0" for="node" attr.name="timestamp" attr.type="int"/>
+              <graph edgedefault="directed">
+                <node id="1"><data key="d0">0</data></node>
+                <node id="2"><data key="d0">4</data></node>
+                <edge source="2" target="1"/>
+              </graph>
+            </graphml>"#;
+        let read = read_graphml(document.as_bytes()).expect("valid document");
+        assert_eq!(Parents::from([1]), read[1].parents);
+        assert_eq!(4, read[1].timestamp);
+    }
+
+    #[test]
+    fn test_read_graphml_invalid_id() {
+        let document = r#"<graphml><graph><node id="n0"/></graph></graphml>"#;
+        let err = read_graphml(document.as_bytes()).expect_err("invalid");
+        assert!(
+            matches!(
+                err,
+                LedgerError::InvalidDocument {
+                    format: "GraphML",
+                    ..
+                }
+            ),
+            "{err}"
+        );
+    }
+}
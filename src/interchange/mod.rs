@@ -0,0 +1,205 @@
+//! Interchange formats that round-trip the [`Vertex`] data, so a ledger can be moved between
+//! `ledger` and external tools. As in the database, the vertex with ID `n` is at index `n - 1`
+//! and the edges point from the approving vertex to its parent.
+
+use std::fmt;
+
+use crate::{
+    error::LedgerError,
+    vertex::{Vertex, MAX_PARENTS},
+};
+
+mod graphml;
+mod node_link;
+pub use graphml::{read_graphml, write_graphml};
+pub use node_link::{read_node_link_json, write_node_link_json};
+
+type Id = usize;
+type Timestamp = u32;
+
+/// Tells which edge of the vertex the exported edge is: the position of the parent. The first
+/// two are labeled `left` and `right` like in the two-parent format, the rest by the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Parent(usize);
+
+impl Parent {
+    fn from_str(value: &str) -> Option<Parent> {
+        match value {
+            "left" => Some(Parent(0)),
+            "right" => Some(Parent(1)),
+            _ => value
+                .parse()
+                .ok()
+                .filter(|position| *position < MAX_PARENTS)
+                .map(Parent),
+        }
+    }
+}
+
+impl fmt::Display for Parent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => write!(f, "left"),
+            1 => write!(f, "right"),
+            position => write!(f, "{position}"),
+        }
+    }
+}
+
+/// An edge from the `source` vertex to its `target` parent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Edge {
+    source: Id,
+    target: Id,
+    parent: Option<Parent>,
+}
+
+fn edges<'a>(vertices: impl IntoIterator<Item = &'a Vertex>) -> Vec<Edge> {
+    vertices
+        .into_iter()
+        .enumerate()
+        .flat_map(|(idx, vertex)| {
+            vertex
+                .parents
+                .into_iter()
+                .enumerate()
+                .map(move |(position, target)| Edge {
+                    source: idx + 1,
+                    target,
+                    parent: Some(Parent(position)),
+                })
+        })
+        .collect()
+}
+
+/// Builds the vertices from the nodes and the edges read from a document. The node IDs must be
+/// `1..=n`. The edges without the parent label take the first free position, and the gaps left
+/// by the labels are closed.
+fn build_vertices(
+    format: &'static str,
+    nodes: Vec<(Id, Timestamp)>,
+    edges: Vec<Edge>,
+) -> Result<Vec<Vertex>, LedgerError> {
+    let invalid = |reason: String| LedgerError::InvalidDocument { format, reason };
+
+    let count = nodes.len();
+    let mut vertices: Vec<Option<Vertex>> = (0..count).map(|_| None).collect();
+    for (id, timestamp) in nodes {
+        let slot = id
+            .checked_sub(1)
+            .and_then(|idx| vertices.get_mut(idx))
+            .ok_or_else(|| invalid(format!("node IDs must be 1..={count}, found {id}")))?;
+        if slot.is_some() {
+            return Err(invalid(format!("duplicated node {id}")));
+        }
+        *slot = Some(Vertex {
+            timestamp,
+            ..Default::default()
+        });
+    }
+    let mut vertices: Vec<Vertex> = vertices
+        .into_iter()
+        .map(Option::unwrap_or_default)
+        .collect();
+
+    let mut parents = vec![[None; MAX_PARENTS]; count];
+    for edge in edges {
+        if edge.target == 0 || edge.target > count {
+            return Err(invalid(format!(
+                "edge to non-existing node {}",
+                edge.target
+            )));
+        }
+        let slots = edge
+            .source
+            .checked_sub(1)
+            .and_then(|idx| parents.get_mut(idx))
+            .ok_or_else(|| invalid(format!("edge from non-existing node {}", edge.source)))?;
+        let position = match edge.parent {
+            Some(Parent(position)) => Some(position),
+            None => slots.iter().position(Option::is_none),
+        };
+        match position.map(|position| &mut slots[position]) {
+            Some(slot @ None) => *slot = Some(edge.target),
+            _ => {
+                return Err(invalid(format!(
+                    "node {} has more than {MAX_PARENTS} parents or duplicated parent labels",
+                    edge.source
+                )))
+            }
+        }
+    }
+    for (vertex, slots) in vertices.iter_mut().zip(parents) {
+        for parent in slots.into_iter().flatten() {
+            vertex.parents.push(parent);
+        }
+    }
+
+    Ok(vertices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vertex::Parents;
+
+    #[test]
+    fn test_build_vertices_unlabeled_edges() {
+        let edges = vec![
+            Edge {
+                source: 2,
+                target: 1,
+                parent: None,
+            },
+            Edge {
+                source: 2,
+                target: 1,
+                parent: None,
+            },
+        ];
+        let vertices = build_vertices("test", vec![(2, 5), (1, 0)], edges).expect("valid");
+        assert_eq!(
+            vec![
+                Vertex::default(),
+                Vertex {
+                    parents: Parents::from([1, 1]),
+                    timestamp: 5
+                }
+            ],
+            vertices
+        );
+    }
+
+    #[test]
+    fn test_build_vertices_labeled_edges() {
+        let edge = |target, parent| Edge {
+            source: 3,
+            target,
+            parent,
+        };
+        let edges = vec![
+            edge(2, Parent::from_str("5")),
+            edge(1, None),
+            edge(2, Parent::from_str("right")),
+        ];
+        let vertices = build_vertices("test", vec![(1, 0), (2, 0), (3, 0)], edges).expect("valid");
+        assert_eq!(Parents::from([1, 2, 2]), vertices[2].parents);
+
+        let edges = vec![edge(1, Parent::from_str("left")), edge(2, Some(Parent(0)))];
+        let err = build_vertices("test", vec![(1, 0), (2, 0), (3, 0)], edges).expect_err("invalid");
+        assert!(
+            err.to_string().contains("duplicated parent labels"),
+            "{err}"
+        );
+        assert_eq!(None, Parent::from_str("8"));
+    }
+
+    #[test]
+    fn test_build_vertices_missing_node() {
+        let err = build_vertices("test", vec![(1, 0), (3, 0)], vec![]).expect_err("invalid");
+        assert!(
+            matches!(err, LedgerError::InvalidDocument { format: "test", .. }),
+            "{err}"
+        );
+    }
+}
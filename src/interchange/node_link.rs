@@ -0,0 +1,156 @@
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{build_vertices, edges, Edge, Id, Parent, Timestamp};
+use crate::{error::LedgerError, vertex::Vertex};
+
+/// The node-link JSON document, as understood by e.g. NetworkX
+#[derive(Debug, Serialize, Deserialize)]
+struct NodeLinkGraph {
+    #[serde(default = "directed")]
+    directed: bool,
+    #[serde(default)]
+    multigraph: bool,
+    #[serde(default)]
+    graph: serde_json::Map<String, serde_json::Value>,
+    nodes: Vec<Node>,
+    #[serde(alias = "edges")]
+    links: Vec<Link>,
+}
+
+fn directed() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Node {
+    id: Id,
+    timestamp: Timestamp,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Link {
+    source: Id,
+    target: Id,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+}
+
+/// Writes the vertices as the node-link JSON. The edges are labeled with the `parent`
+/// attribute (`left`, `right` or the position of the parent from 2). More edges can point to the
+/// same parent, so the graph is a multigraph.
+pub fn write_node_link_json<'a>(
+    vertices: impl IntoIterator<Item = &'a Vertex> + Clone,
+    writer: impl Write,
+) -> Result<(), LedgerError> {
+    let document = NodeLinkGraph {
+        directed: true,
+        multigraph: true,
+        graph: Default::default(),
+        nodes: vertices
+            .clone()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, vertex)| Node {
+                id: idx + 1,
+                timestamp: vertex.timestamp,
+            })
+            .collect(),
+        links: edges(vertices)
+            .into_iter()
+            .map(|edge| Link {
+                source: edge.source,
+                target: edge.target,
+                parent: edge.parent.map(|parent| parent.to_string()),
+            })
+            .collect(),
+    };
+    serde_json::to_writer_pretty(writer, &document)?;
+    Ok(())
+}
+
+/// Reads the vertices from the node-link JSON written by [`write_node_link_json`] or an
+/// external tool. The `parent` attribute of the links is optional.
+pub fn read_node_link_json(reader: impl Read) -> Result<Vec<Vertex>, LedgerError> {
+    let document: NodeLinkGraph = serde_json::from_reader(reader)?;
+
+    let nodes = document
+        .nodes
+        .into_iter()
+        .map(|node| (node.id, node.timestamp))
+        .collect();
+    let edges = document
+        .links
+        .into_iter()
+        .map(|link| {
+            let parent =
+                match link.parent.as_deref() {
+                    None => None,
+                    Some(label) => Some(Parent::from_str(label).ok_or_else(|| {
+                        LedgerError::InvalidDocument {
+                            format: "node-link JSON",
+                            reason: format!("unknown parent label '{label}'"),
+                        }
+                    })?),
+                };
+            Ok(Edge {
+                source: link.source,
+                target: link.target,
+                parent,
+            })
+        })
+        .collect::<Result<_, LedgerError>>()?;
+
+    build_vertices("node-link JSON", nodes, edges)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vertex::Parents;
+
+    fn vertices() -> Vec<Vertex> {
+        vec![
+            Vertex::default(),
+            Vertex {
+                parents: Parents::from([1, 1]),
+                timestamp: 3,
+            },
+            Vertex {
+                parents: Parents::from([2]),
+                timestamp: 7,
+            },
+            Vertex {
+                parents: Parents::from([3, 1, 2]),
+                timestamp: 8,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_node_link_json_round_trip() {
+        let mut output = vec![];
+        write_node_link_json(&vertices(), &mut output).expect("writing to vector");
+        let read = read_node_link_json(output.as_slice()).expect("valid document");
+        assert_eq!(vertices(), read);
+    }
+
+    #[test]
+    fn test_read_node_link_json_external() {
+        let document = r#"{
+            "directed": true,
+            "nodes": [{"id": 1, "timestamp": 0}, {"id": 2, "timestamp": 4}],
+            "edges": [{"source": 2, "target": 1}]
+        }"#;
+        let read = read_node_link_json(document.as_bytes()).expect("valid document");
+        assert_eq!(Parents::from([1]), read[1].parents);
+        assert_eq!(4, read[1].timestamp);
+    }
+
+    #[test]
+    fn test_read_node_link_json_invalid() {
+        let err = read_node_link_json("{\"nodes\": 1}".as_bytes()).expect_err("invalid");
+        assert!(matches!(err, LedgerError::Json(_)), "{err}");
+    }
+}
@@ -1,3 +1,14 @@
+pub mod binary_database;
 pub mod database;
+pub mod diagnostics;
+pub mod diff;
+pub mod error;
+pub mod export;
+pub mod follow;
+pub mod generator;
 pub mod graph;
+pub mod interchange;
+pub mod prune;
+#[cfg(test)]
+mod test_util;
 pub mod vertex;
@@ -0,0 +1,278 @@
+//! Pruning of the old history of the ledger. The graph is cut at a timestamp or a root depth,
+//! and the vertices before the cut are removed, except for the entry points: the pruned parents
+//! of the kept vertices. They lose their own parents and become the genesis vertices of the new
+//! database, so its statistics are the ones of the retained part of the ledger.
+
+use std::io::{self, Write};
+
+use crate::{
+    graph::Graph,
+    vertex::{Parents, Vertex},
+};
+
+type Id = usize;
+type Timestamp = u32;
+
+/// The root of the database, which isn't stored in it
+const ROOT_ID: Id = 1;
+
+/// Where the graph is cut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cut {
+    /// The vertices older than the timestamp are pruned
+    Timestamp(Timestamp),
+    /// The vertices nearer to the roots than the depth are pruned. The vertices unreachable
+    /// from the roots are kept.
+    RootDepth(usize),
+}
+
+/// The new database made from the vertices kept after pruning
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    /// The entry points, then the kept vertices in the original order. The vertex with ID 1 is
+    /// the original root if it's kept, otherwise the oldest entry point, whose timestamp is set
+    /// to 0 as the root isn't stored in the database.
+    pub vertices: Vec<Vertex>,
+    /// The new IDs of the entry points. They have no parents and keep their timestamps.
+    pub entry_points: Vec<Id>,
+    /// The new IDs of the entry points and the kept original roots. Analyzed with these roots,
+    /// the snapshot has the same statistics as the retained part of the original graph.
+    pub roots: Vec<Id>,
+    /// The original and the new ID of every vertex of the snapshot, ordered by the new ID
+    pub ids: Vec<(Id, Id)>,
+}
+
+impl Snapshot {
+    /// Writes the ID mapping as CSV with the header `old_id,new_id,entry_point`
+    pub fn write_id_mapping(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "old_id,new_id,entry_point")?;
+        for (old_id, new_id) in &self.ids {
+            let entry_point = self.entry_points.binary_search(new_id).is_ok();
+            writeln!(writer, "{old_id},{new_id},{entry_point}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Prunes the vertices before the cut and renumbers the rest densely. The approvers of the kept
+/// vertices are kept too, so the pruned vertices always form a past cone. It must be called
+/// after [`Graph::walk_and_analyze`].
+pub fn prune(graph: &Graph, cut: Cut) -> Snapshot {
+    let vertices = &graph.graph;
+    let mut kept: Vec<bool> = vertices
+        .iter()
+        .map(|vertex| match cut {
+            Cut::Timestamp(timestamp) => vertex.vertex.timestamp >= timestamp,
+            Cut::RootDepth(depth) => !vertex.visited || vertex.root_depth >= depth,
+        })
+        .collect();
+    // the timestamps or the depths don't have to grow along the edges
+    let mut queue: Vec<Id> = (1..=vertices.len()).filter(|id| kept[id - 1]).collect();
+    while let Some(id) = queue.pop() {
+        for approver in graph.approvers(id) {
+            if !kept[approver - 1] {
+                kept[approver - 1] = true;
+                queue.push(approver);
+            }
+        }
+    }
+
+    let mut entry_point = vec![false; vertices.len()];
+    for (vertex, _) in vertices.iter().zip(&kept).filter(|(_, kept)| **kept) {
+        for parent in vertex.vertex.parents.iter() {
+            entry_point[parent - 1] |= !kept[parent - 1];
+        }
+    }
+
+    // the oldest entry point takes the place of the root, so losing its timestamp doesn't
+    // change the latest one
+    let first = match kept[ROOT_ID - 1] {
+        true => Some(ROOT_ID - 1),
+        false => (0..vertices.len())
+            .filter(|idx| entry_point[*idx])
+            .min_by_key(|idx| vertices[*idx].vertex.timestamp),
+    };
+    // the entry points go first, so no parent is moved after its approvers
+    let order: Vec<usize> = first
+        .into_iter()
+        .chain((0..vertices.len()).filter(|idx| entry_point[*idx] && Some(*idx) != first))
+        .chain((0..vertices.len()).filter(|idx| kept[*idx] && Some(*idx) != first))
+        .collect();
+    let mut new_ids = vec![None; vertices.len()];
+    let mut snapshot = Snapshot::default();
+    for (new_id, idx) in (ROOT_ID..).zip(&order) {
+        new_ids[*idx] = Some(new_id);
+        snapshot.ids.push((idx + 1, new_id));
+        if entry_point[*idx] || (kept[*idx] && graph.roots().contains(&(idx + 1))) {
+            snapshot.roots.push(new_id);
+        }
+        if entry_point[*idx] {
+            snapshot.entry_points.push(new_id);
+        }
+    }
+
+    snapshot.vertices = order
+        .iter()
+        .map(|idx| {
+            let vertex = &vertices[*idx].vertex;
+            if entry_point[*idx] {
+                return Vertex {
+                    parents: Parents::default(),
+                    timestamp: vertex.timestamp,
+                };
+            }
+            let mut parents = Parents::default();
+            for parent in vertex.parents.iter() {
+                parents.push(new_ids[parent - 1].expect("the parent is kept or an entry point"));
+            }
+            Vertex {
+                parents,
+                timestamp: vertex.timestamp,
+            }
+        })
+        .collect();
+    match snapshot.vertices.first_mut() {
+        Some(root) => root.timestamp = 0,
+        // nothing is kept, so only the root is left
+        None => snapshot.vertices.push(Vertex::default()),
+    }
+    if snapshot.roots.is_empty() {
+        snapshot.roots.push(ROOT_ID);
+    }
+    snapshot
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{graph::UnreachablePolicy, test_util::graph};
+
+    use super::*;
+
+    /// Checks that the snapshot analyzed with its roots has the same statistics as the retained
+    /// part of the original graph: the entry points without their parents and the kept vertices
+    fn assert_retained_stats(original: &Graph, snapshot: &Snapshot) {
+        let new_ids: HashMap<Id, Id> = snapshot.ids.iter().copied().collect();
+        let retained = original
+            .graph
+            .iter()
+            .enumerate()
+            .map(|(idx, vertex)| match new_ids.get(&(idx + 1)) {
+                Some(new_id) if !snapshot.entry_points.contains(new_id) => vertex.vertex.clone(),
+                // the other pruned vertices are disconnected, so they aren't reached
+                _ => Vertex {
+                    parents: Parents::default(),
+                    timestamp: vertex.vertex.timestamp,
+                },
+            })
+            .collect();
+        let roots = snapshot
+            .ids
+            .iter()
+            .filter(|(_, new_id)| snapshot.roots.contains(new_id))
+            .map(|(old_id, _)| *old_id)
+            .collect();
+        let mut retained = Graph::new(retained)
+            .with_roots(roots)
+            .with_unreachable_policy(UnreachablePolicy::Exclude);
+        retained.walk_and_analyze().expect("valid graph");
+        let mut expected = retained.stats();
+        expected.vertices -= expected.unreachable_vertices;
+        expected.unreachable_vertices = 0;
+
+        let mut pruned = Graph::new(snapshot.vertices.clone()).with_roots(snapshot.roots.clone());
+        pruned.walk_and_analyze().expect("valid graph");
+        assert_eq!(expected, pruned.stats());
+        for (old_id, new_id) in &snapshot.ids {
+            let approvers: Vec<Id> = original
+                .approvers(*old_id)
+                .filter_map(|approver| new_ids.get(&approver).copied())
+                .filter(|approver| !snapshot.entry_points.contains(approver))
+                .collect();
+            assert_eq!(approvers, pruned.approvers(*new_id).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_prune_before_timestamp() {
+        let original = graph(&[
+            (&[], 0),
+            (&[1, 1], 0),
+            (&[1, 2], 0),
+            (&[2], 1),
+            (&[3], 2),
+            (&[3, 4], 3),
+        ]);
+        let snapshot = prune(&original, Cut::Timestamp(1));
+        assert_eq!(vec![1, 2], snapshot.entry_points);
+        assert_eq!(vec![1, 2], snapshot.roots);
+        assert_eq!(vec![(2, 1), (3, 2), (4, 3), (5, 4), (6, 5)], snapshot.ids);
+        assert_eq!(Parents::default(), snapshot.vertices[1].parents);
+        assert_eq!(Parents::from([2, 3]), snapshot.vertices[4].parents);
+        assert_retained_stats(&original, &snapshot);
+
+        let snapshot = prune(&original, Cut::Timestamp(2));
+        assert_eq!(vec![1, 2], snapshot.entry_points);
+        assert_eq!(vec![(3, 1), (4, 2), (5, 3), (6, 4)], snapshot.ids);
+        assert_retained_stats(&original, &snapshot);
+
+        let mut mapping = vec![];
+        snapshot
+            .write_id_mapping(&mut mapping)
+            .expect("writing to vector");
+        assert_eq!(
+            "old_id,new_id,entry_point\n3,1,true\n4,2,true\n5,3,false\n6,4,false\n",
+            String::from_utf8(mapping).expect("valid UTF-8")
+        );
+    }
+
+    #[test]
+    fn test_prune_keeps_approvers() {
+        // 3 is older than its parent, but it approves the kept vertex 2
+        let original = graph(&[(&[], 0), (&[1], 5), (&[2], 3), (&[1], 1)]);
+        let snapshot = prune(&original, Cut::Timestamp(4));
+        assert_eq!(vec![(1, 1), (2, 2), (3, 3)], snapshot.ids);
+        assert_eq!(vec![1], snapshot.entry_points);
+        assert_eq!(
+            vec![
+                Vertex::default(),
+                Vertex {
+                    parents: Parents::from([1]),
+                    timestamp: 5
+                },
+                Vertex {
+                    parents: Parents::from([2]),
+                    timestamp: 3
+                },
+            ],
+            snapshot.vertices
+        );
+        assert_retained_stats(&original, &snapshot);
+    }
+
+    #[test]
+    fn test_prune_before_root_depth() {
+        let original = graph(&[(&[], 0), (&[1], 1), (&[2], 2), (&[3, 1], 3), (&[3], 4)]);
+        let snapshot = prune(&original, Cut::RootDepth(2));
+        // 4 is at depth 1, but it approves 3, so it's kept and its parent 1 is an entry point
+        assert_eq!(vec![(1, 1), (2, 2), (3, 3), (4, 4), (5, 5)], snapshot.ids);
+        assert_eq!(vec![1, 2], snapshot.entry_points);
+        assert_eq!(Parents::default(), snapshot.vertices[1].parents);
+        assert_eq!(Parents::from([3, 1]), snapshot.vertices[3].parents);
+        assert_retained_stats(&original, &snapshot);
+
+        let snapshot = prune(&original, Cut::RootDepth(0));
+        assert!(snapshot.entry_points.is_empty());
+        assert_eq!(vec![1], snapshot.roots);
+        assert_eq!(
+            original
+                .graph
+                .iter()
+                .map(|vertex| vertex.vertex.clone())
+                .collect::<Vec<_>>(),
+            snapshot.vertices
+        );
+        assert_retained_stats(&original, &snapshot);
+    }
+}
@@ -0,0 +1,33 @@
+//! The fixtures shared by the unit tests
+
+use crate::{
+    graph::Graph,
+    vertex::{Parents, Vertex},
+};
+
+type Id = usize;
+type Timestamp = u32;
+
+/// Creates the vertex with the timestamp 0
+pub fn vertex(parents: &[Id]) -> Vertex {
+    vertex_at(parents, 0)
+}
+
+pub fn vertex_at(parents: &[Id], timestamp: Timestamp) -> Vertex {
+    Vertex {
+        parents: Parents::from_slice(parents).expect("at most 8 parents"),
+        timestamp,
+    }
+}
+
+/// Creates the graph of the vertices given as the parents and the timestamp, and analyzes it
+pub fn graph(vertices: &[(&[Id], Timestamp)]) -> Graph {
+    let mut graph = Graph::new(
+        vertices
+            .iter()
+            .map(|(parents, timestamp)| vertex_at(parents, *timestamp))
+            .collect(),
+    );
+    graph.walk_and_analyze().expect("valid graph");
+    graph
+}
@@ -1,51 +1,187 @@
-use anyhow::{anyhow, bail, Context};
-use itertools::Itertools;
+use std::{array, fmt, iter, num::ParseIntError, ops::Deref};
+
+use crate::error::{LedgerError, ParseError, RowError};
 
 type Id = usize;
 type Timestamp = u32;
 
-#[derive(Debug, PartialEq, Default, PartialOrd)]
+/// The maximum number of parents of a vertex
+pub const MAX_PARENTS: usize = 8;
+
+/// The format of the database rows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowFormat {
+    /// `left right timestamp`
+    #[default]
+    TwoParents,
+    /// `count parent... timestamp` with up to [`MAX_PARENTS`] parents
+    ParentCount,
+}
+
+/// The IDs of the parents of a vertex, kept inline so the vertex doesn't allocate. The same
+/// parent can be referenced more than once.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Parents {
+    ids: [Id; MAX_PARENTS],
+    len: u8,
+}
+
+impl Parents {
+    /// Creates the parents from the slice or returns `None` if it's longer than [`MAX_PARENTS`]
+    pub fn from_slice(ids: &[Id]) -> Option<Parents> {
+        let mut parents = Parents::default();
+        parents.ids.get_mut(..ids.len())?.copy_from_slice(ids);
+        parents.len = ids.len() as u8;
+        Some(parents)
+    }
+
+    /// Adds the parent
+    ///
+    /// # Panics
+    ///
+    /// If the vertex already has [`MAX_PARENTS`] parents
+    pub fn push(&mut self, id: Id) {
+        assert!(self.len() < MAX_PARENTS, "too many parents");
+        self.ids[self.len()] = id;
+        self.len += 1;
+    }
+}
+
+impl<const N: usize> From<[Id; N]> for Parents {
+    fn from(ids: [Id; N]) -> Self {
+        const { assert!(N <= MAX_PARENTS, "too many parents") };
+        Parents::from_slice(&ids).expect("checked at compile time")
+    }
+}
+
+impl Deref for Parents {
+    type Target = [Id];
+
+    fn deref(&self) -> &Self::Target {
+        &self.ids[..self.len as usize]
+    }
+}
+
+impl IntoIterator for Parents {
+    type Item = Id;
+    type IntoIter = iter::Take<array::IntoIter<Id, MAX_PARENTS>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ids.into_iter().take(self.len())
+    }
+}
+
+impl fmt::Debug for Parents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, PartialOrd)]
 pub struct Vertex {
-    pub left: Option<Id>,
-    pub right: Option<Id>,
+    pub parents: Parents,
     pub timestamp: Timestamp,
 }
 
 impl Vertex {
-    pub fn from_str(str: impl AsRef<str>, id: usize) -> Result<Vertex, anyhow::Error> {
-        let value_str = str.as_ref();
-        let chunks: Vec<&str> = value_str
-            .split_ascii_whitespace()
-            .enumerate()
-            .map(|(i, chunk)| {
-                if i == 3 {
-                    Err(anyhow!("the row has too many items: '{value_str}'"))
-                } else {
-                    Ok(chunk)
+    /// Parses the database row `left right timestamp` of the vertex with the given ID. The ID
+    /// is also the line number in the database file, so it's used to locate the errors.
+    pub fn from_str(str: impl AsRef<str>, id: usize) -> Result<Vertex, LedgerError> {
+        Ok(Vertex::parse_row(str.as_ref(), id)?)
+    }
+
+    /// Like [`Vertex::from_str`], but doesn't allocate
+    pub fn parse_row(value_str: &str, id: usize) -> Result<Vertex, ParseError> {
+        Vertex::parse_row_with_format(value_str, id, RowFormat::TwoParents)
+    }
+
+    /// Parses the database row in the given format without allocating. In both formats the
+    /// parent with the ID of the vertex itself is the missing edge.
+    pub fn parse_row_with_format(
+        value_str: &str,
+        id: usize,
+        format: RowFormat,
+    ) -> Result<Vertex, ParseError> {
+        let malformed = |column: usize, kind: RowError| ParseError::MalformedRow {
+            line: id,
+            column,
+            kind,
+        };
+
+        // the number of items is known once the parent count is parsed
+        let mut expected = match format {
+            RowFormat::TwoParents => 3,
+            RowFormat::ParentCount => 1,
+        };
+        let mut chunks = [(0, ""); MAX_PARENTS + 2];
+        let mut count = 0;
+        for chunk in value_str.split_ascii_whitespace() {
+            let column = column_of(value_str, chunk);
+            if count == expected {
+                return Err(malformed(column, RowError::TooManyItems));
+            }
+            chunks[count] = (column, chunk);
+            count += 1;
+
+            if format == RowFormat::ParentCount && count == 1 {
+                let parents: usize = chunk
+                    .parse()
+                    .map_err(|err| malformed(column, RowError::InvalidParentCount(err)))?;
+                if parents > MAX_PARENTS {
+                    return Err(malformed(column, RowError::TooManyParents(parents)));
                 }
-            })
-            .try_collect()?;
+                expected = parents + 2;
+            }
+        }
 
-        if chunks.len() != 3 {
-            bail!("the row has too few items: '{value_str}'")
+        if count != expected {
+            return Err(malformed(value_str.len() + 1, RowError::TooFewItems));
         }
 
-        let left_id: Id = chunks[0].parse().context("unable to parse the left ID")?;
-        let right_id: Id = chunks[1].parse().context("unable to parse the right ID")?;
-        let timestamp: Timestamp = chunks[2].parse().context("unable to parse the timestamp")?;
+        let (parent_chunks, timestamp_chunk) = chunks[..count].split_at(count - 1);
+        let mut parents = Parents::default();
+        for (idx, (column, chunk)) in parent_chunks.iter().enumerate() {
+            let kind: fn(ParseIntError) -> RowError = match (format, idx) {
+                (RowFormat::TwoParents, 0) => RowError::InvalidLeftId,
+                (RowFormat::TwoParents, _) => RowError::InvalidRightId,
+                (RowFormat::ParentCount, 0) => continue,
+                (RowFormat::ParentCount, _) => RowError::InvalidParentId,
+            };
+            let parent: Id = chunk.parse().map_err(|err| malformed(*column, kind(err)))?;
+            // if node is self-referenced, the edge doesn't exist
+            if parent != id {
+                parents.push(parent);
+            }
+        }
+        let (column, chunk) = timestamp_chunk[0];
+        let timestamp: Timestamp = chunk
+            .parse()
+            .map_err(|err| malformed(column, RowError::InvalidTimestamp(err)))?;
 
-        // if node is self-referenced, the edge doesn't exist
-        let left = if left_id == id { None } else { Some(left_id) };
-        let right = if right_id == id { None } else { Some(right_id) };
+        Ok(Vertex { parents, timestamp })
+    }
+}
 
-        Ok(Vertex {
-            left,
-            right,
-            timestamp,
-        })
+impl RowFormat {
+    /// Returns the items of the valid row that are the parents, including the missing edges
+    pub(crate) fn parent_items(self, row: &str) -> impl Iterator<Item = &str> {
+        let mut items = row.split_ascii_whitespace();
+        let parents = match self {
+            RowFormat::TwoParents => 2,
+            RowFormat::ParentCount => items
+                .next()
+                .and_then(|count| count.parse().ok())
+                .unwrap_or_default(),
+        };
+        items.take(parents)
     }
 }
 
+/// Returns the 1-based column at which `chunk` (a sub-slice of `line`) starts
+pub(crate) fn column_of(line: &str, chunk: &str) -> usize {
+    chunk.as_ptr() as usize - line.as_ptr() as usize + 1
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -57,14 +193,56 @@ mod test {
         let result = Vertex::from_str(input, 1).expect("shouldn't fail");
         assert_eq!(
             Vertex {
-                left: Some(0),
-                right: None,
+                parents: Parents::from([0]),
                 timestamp: 2
             },
             result
         );
     }
 
+    #[test]
+    fn parse_row_parent_count() {
+        let vertex = Vertex::parse_row_with_format("3 1 4 2 7", 4, RowFormat::ParentCount)
+            .expect("shouldn't fail");
+        assert_eq!(
+            Vertex {
+                parents: Parents::from([1, 2]),
+                timestamp: 7
+            },
+            vertex
+        );
+
+        let vertex = Vertex::parse_row_with_format("0 7", 4, RowFormat::ParentCount)
+            .expect("shouldn't fail");
+        assert!(vertex.parents.is_empty());
+    }
+
+    #[test]
+    fn parse_row_parent_count_malformed() {
+        let parse = |row| {
+            let err = Vertex::parse_row_with_format(row, 9, RowFormat::ParentCount)
+                .expect_err("parsing error");
+            match err {
+                ParseError::MalformedRow { column, kind, .. } => (column, kind),
+                ParseError::Io(err) => panic!("{err}"),
+            }
+        };
+        assert!(matches!(
+            parse("9 1 1 1 1 1 1 1 1 1 0"),
+            (1, RowError::TooManyParents(9))
+        ));
+        assert!(matches!(
+            parse("x 1 0"),
+            (1, RowError::InvalidParentCount(_))
+        ));
+        assert!(matches!(
+            parse("2 1 x 0"),
+            (5, RowError::InvalidParentId(_))
+        ));
+        assert!(matches!(parse("2 1 1"), (6, RowError::TooFewItems)));
+        assert!(matches!(parse("1 1 1 1"), (7, RowError::TooManyItems)));
+    }
+
     #[test]
     fn try_from_sting_negative_integer() {
         let input = "0 1 -2";
@@ -73,7 +251,15 @@ mod test {
         assert!(
             err.to_string().contains("unable to parse the timestamp"),
             "{err}"
-        )
+        );
+        assert!(matches!(
+            err,
+            LedgerError::MalformedRow {
+                line: 1,
+                column: 5,
+                kind: RowError::InvalidTimestamp(_)
+            }
+        ));
     }
 
     #[test]
@@ -93,7 +279,15 @@ mod test {
         assert!(
             err.to_string().contains("the row has too many items"),
             "{err}"
-        )
+        );
+        assert!(matches!(
+            err,
+            LedgerError::MalformedRow {
+                line: 2,
+                column: 7,
+                kind: RowError::TooManyItems
+            }
+        ));
     }
 
     #[test]
@@ -1,5 +1,28 @@
 #[cfg(test)]
 mod test {
+    use std::process::{Command, Output};
+
+    /// Runs the binary and returns its output
+    fn run(args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_ledger"))
+            .args(args)
+            .output()
+            .expect("running the binary failed")
+    }
+
+    /// Runs the `stats` command on `database.txt` and returns its standard output
+    fn stats(args: &[&str]) -> String {
+        let output = run(&[&["stats", "database.txt"], args].concat());
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).expect("the output isn't valid UTF-8")
+    }
+
+    /// Writes the database to a temporary file named after the test
+    fn temp_database(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ledger-{name}-{}.txt", std::process::id()));
+        std::fs::write(&path, content).expect("writing database failed");
+        path.to_str().expect("valid UTF-8 path").to_string()
+    }
 
     #[test]
     fn test_integration() {
@@ -15,5 +38,311 @@ mod test {
         assert_eq!(avg_inbound_ref_per_node, 1.6666666666666667);
         assert_eq!(avg_nodes_per_depth, 2.5);
         assert_eq!(avg_depth_per_node, 1.3333333333333333);
+
+        assert_eq!(graph.tips(), vec![5, 6]);
+        assert_eq!(graph.calc_tip_ratio(), 0.3333333333333333);
+        assert_eq!(graph.calc_avg_tip_age(), 0.5);
+
+        let stats = graph.stats();
+        assert_eq!(stats.vertices, 6);
+        assert_eq!(stats.avg_root_depth_per_node, avg_depth_per_node);
+        assert_eq!(stats.avg_nodes_per_root_depth, avg_nodes_per_depth);
+        assert_eq!(stats.avg_inbound_ref_per_node, avg_inbound_ref_per_node);
+        assert_eq!(stats.tips, 2);
+    }
+
+    #[test]
+    fn test_stats_text() {
+        assert_eq!(
+            "AVG DAG DEPTH: 1.33
+AVG NODES PER DEPTH:  2.50
+AVG REF:  1.67
+TIPS: 2
+TIP RATIO: 0.33
+AVG TIP AGE: 0.50
+",
+            stats(&[])
+        );
+        assert_eq!(
+            "AVG DAG DEPTH: 0.83
+AVG NODES PER DEPTH:  2.00
+AVG REF:  1.67
+TIPS: 2
+TIP RATIO: 0.33
+AVG TIP AGE: 0.50
+
+ROOT: 1
+AVG DAG DEPTH: 1.00
+AVG NODES PER DEPTH:  1.50
+AVG REF:  2.25
+TIPS: 1
+TIP RATIO: 0.25
+AVG TIP AGE: 0.00
+
+ROOT: 4
+AVG DAG DEPTH: 0.50
+AVG NODES PER DEPTH:  1.00
+AVG REF:  0.50
+TIPS: 1
+TIP RATIO: 0.50
+AVG TIP AGE: 0.00
+",
+            stats(&["--root", "1,4", "--per-root"])
+        );
+    }
+
+    #[test]
+    fn test_stats_json() {
+        assert_eq!(
+            r#"{
+  "vertices": 6,
+  "unreachable_vertices": 0,
+  "avg_root_depth_per_node": 1.3333333333333333,
+  "avg_nodes_per_root_depth": 2.5,
+  "avg_inbound_ref_per_node": 1.6666666666666667,
+  "tips": 2,
+  "tip_ratio": 0.3333333333333333,
+  "avg_tip_age": 0.5
+}
+"#,
+            stats(&["--format", "json"])
+        );
+        assert_eq!(
+            r#"{
+  "vertices": 6,
+  "unreachable_vertices": 0,
+  "avg_root_depth_per_node": 0.8333333333333334,
+  "avg_nodes_per_root_depth": 2.0,
+  "avg_inbound_ref_per_node": 1.6666666666666667,
+  "tips": 2,
+  "tip_ratio": 0.3333333333333333,
+  "avg_tip_age": 0.5,
+  "roots": [
+    {
+      "root": 1,
+      "vertices": 4,
+      "unreachable_vertices": 0,
+      "avg_root_depth_per_node": 1.0,
+      "avg_nodes_per_root_depth": 1.5,
+      "avg_inbound_ref_per_node": 2.25,
+      "tips": 1,
+      "tip_ratio": 0.25,
+      "avg_tip_age": 0.0
+    },
+    {
+      "root": 4,
+      "vertices": 2,
+      "unreachable_vertices": 0,
+      "avg_root_depth_per_node": 0.5,
+      "avg_nodes_per_root_depth": 1.0,
+      "avg_inbound_ref_per_node": 0.5,
+      "tips": 1,
+      "tip_ratio": 0.5,
+      "avg_tip_age": 0.0
+    }
+  ]
+}
+"#,
+            stats(&["--format", "json", "--root", "1,4", "--per-root"])
+        );
+    }
+
+    #[test]
+    fn test_stats_csv() {
+        assert_eq!(
+            "vertices,unreachable_vertices,avg_root_depth_per_node,avg_nodes_per_root_depth,avg_inbound_ref_per_node,tips,tip_ratio,avg_tip_age
+6,0,1.3333333333333333,2.5,1.6666666666666667,2,0.3333333333333333,0.5
+",
+            stats(&["--format", "csv"])
+        );
+        // the overall statistics have no root
+        assert_eq!(
+            "root,vertices,unreachable_vertices,avg_root_depth_per_node,avg_nodes_per_root_depth,avg_inbound_ref_per_node,tips,tip_ratio,avg_tip_age
+,6,0,0.8333333333333334,2,1.6666666666666667,2,0.3333333333333333,0.5
+1,4,0,1,1.5,2.25,1,0.25,0
+4,2,0,0.5,1,0.5,1,0.5,0
+",
+            stats(&["--format", "csv", "--root", "1,4", "--per-root"])
+        );
+    }
+
+    #[test]
+    fn test_stats_toml() {
+        assert_eq!(
+            "vertices = 6
+unreachable_vertices = 0
+avg_root_depth_per_node = 1.3333333333333333
+avg_nodes_per_root_depth = 2.5
+avg_inbound_ref_per_node = 1.6666666666666667
+tips = 2
+tip_ratio = 0.3333333333333333
+avg_tip_age = 0.5
+",
+            stats(&["--format", "toml"])
+        );
+        assert_eq!(
+            "vertices = 6
+unreachable_vertices = 0
+avg_root_depth_per_node = 0.8333333333333334
+avg_nodes_per_root_depth = 2.0
+avg_inbound_ref_per_node = 1.6666666666666667
+tips = 2
+tip_ratio = 0.3333333333333333
+avg_tip_age = 0.5
+
+[[roots]]
+root = 1
+vertices = 4
+unreachable_vertices = 0
+avg_root_depth_per_node = 1.0
+avg_nodes_per_root_depth = 1.5
+avg_inbound_ref_per_node = 2.25
+tips = 1
+tip_ratio = 0.25
+avg_tip_age = 0.0
+
+[[roots]]
+root = 4
+vertices = 2
+unreachable_vertices = 0
+avg_root_depth_per_node = 0.5
+avg_nodes_per_root_depth = 1.0
+avg_inbound_ref_per_node = 0.5
+tips = 1
+tip_ratio = 0.5
+avg_tip_age = 0.0
+",
+            stats(&["--format", "toml", "--root", "1,4", "--per-root"])
+        );
+    }
+
+    #[test]
+    fn test_stats_forward_reference() {
+        // vertex 2 references vertex 3
+        let path = temp_database("forward-reference", "3\n1 3 1\n1 1 0\n2 3 2\n");
+        let output = run(&["stats", &path, "--format", "csv"]);
+        std::fs::remove_file(&path).expect("removing database failed");
+
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            "warning: forward reference: vertex 2 references vertex 3\n",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(
+            "vertices,unreachable_vertices,avg_root_depth_per_node,avg_nodes_per_root_depth,avg_inbound_ref_per_node,tips,tip_ratio,avg_tip_age
+4,0,1,1.5,1.5,1,0.25,0
+",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    #[test]
+    fn test_query_invalid_id() {
+        let output = run(&["query", "database.txt", "--id", "9"]);
+        assert_eq!(Some(1), output.status.code());
+        assert_eq!(
+            "vertex with ID 9 doesn't exist. Max number is 6\n",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn test_cone_invalid_id() {
+        let output = run(&["cone", "database.txt", "--id", "9"]);
+        assert_eq!(Some(1), output.status.code());
+        assert_eq!(
+            "vertex with ID 9 doesn't exist. Max number is 6\n",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn test_diff_pruned() {
+        let temp = |name: &str| {
+            let path = std::env::temp_dir().join(format!("ledger-{name}-{}", std::process::id()));
+            path.to_str().expect("valid UTF-8 path").to_string()
+        };
+        let (pruned, ids) = (temp("pruned.txt"), temp("ids.csv"));
+        let output = run(&[
+            "prune",
+            "database.txt",
+            "--before",
+            "1",
+            "-o",
+            &pruned,
+            "--id-mapping",
+            &ids,
+        ]);
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            "roots of the pruned database: --root 1,2\n",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // the entry point 2 isn't reachable from the default root
+        let output = run(&["diff", "database.txt", &pruned]);
+        assert_eq!(Some(1), output.status.code());
+        assert!(output.stdout.is_empty());
+        assert_eq!(
+            format!("{pruned}: vertices unreachable from the root: [2, 4]\n"),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let output = run(&["diff", "database.txt", &pruned, "--after-root", "1,2"]);
+        std::fs::remove_file(&pruned).expect("removing database failed");
+        std::fs::remove_file(&ids).expect("removing ID mapping failed");
+        assert_eq!(Some(1), output.status.code());
+        assert!(output.stderr.is_empty(), "{output:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("  vertices: 6.00 -> 5.00 (-1.00)\n")
+        );
+    }
+
+    #[test]
+    fn test_validate_options() {
+        // vertex 2 has no parents
+        let path = temp_database("validate-unreachable", "2\n2 2 0\n1 1 1\n");
+        let failed = run(&["validate", &path]);
+        let excluded = run(&["validate", &path, "--unreachable", "exclude"]);
+        let with_root = run(&["validate", &path, "--root", "1,2"]);
+        std::fs::remove_file(&path).expect("removing database failed");
+        assert_eq!(Some(1), failed.status.code());
+        assert!(excluded.status.success(), "{excluded:?}");
+        assert_eq!(
+            "vertices unreachable from the root: [2]\n",
+            String::from_utf8_lossy(&excluded.stderr)
+        );
+        assert!(with_root.status.success(), "{with_root:?}");
+
+        // vertex 3 is older than its parent
+        let path = temp_database("validate-timestamps", "2\n1 1 5\n2 2 3\n");
+        let unchecked = run(&["validate", &path]);
+        let warned = run(&["validate", &path, "--validate-timestamps", "warn"]);
+        let strict = run(&["validate", &path, "--validate-timestamps", "strict"]);
+        std::fs::remove_file(&path).expect("removing database failed");
+        assert!(unchecked.status.success(), "{unchecked:?}");
+        assert!(unchecked.stderr.is_empty());
+        assert!(warned.status.success(), "{warned:?}");
+        assert_eq!(
+            "line 3: timestamp 3 is earlier than the timestamp 5 of the parent 2\n",
+            String::from_utf8_lossy(&warned.stderr)
+        );
+        assert_eq!(Some(1), strict.status.code());
+    }
+
+    #[test]
+    fn test_stats_roots_without_first_vertex() {
+        // the genesis vertices 4 and 7 of a snapshot, which doesn't use the vertex 1
+        let path = temp_database("snapshot", "6\n4 2 0\n2 7 1\n4 4 0\n4 5 1\n3 5 2\n7 7 0\n");
+        let output = run(&["stats", &path, "--root", "4,7", "--format", "csv"]);
+        std::fs::remove_file(&path).expect("removing database failed");
+
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            "vertices,unreachable_vertices,avg_root_depth_per_node,avg_nodes_per_root_depth,avg_inbound_ref_per_node,tips,tip_ratio,avg_tip_age
+6,0,0.8333333333333334,2,1,1,0.16666666666666666,0
+",
+            String::from_utf8_lossy(&output.stdout)
+        );
     }
 }